@@ -0,0 +1,104 @@
+// Headless engine matches, for comparing two engine settings without the GUI.
+// cargo run --release -- --match 10 0.5 2.0:3
+
+use std::sync::atomic::AtomicBool;
+
+use crate::engine;
+
+/// Games without a result after this many plies are scored as draw;
+/// repetitions and the fifty-move rule are not adjudicated.
+const MAX_PLIES: u16 = 300;
+
+/// Settings for one side of a match.
+#[derive(Clone, Copy, Debug)]
+pub struct EngineConfig {
+    pub secs_per_move: f32,
+    pub aggression: i16,
+}
+
+impl EngineConfig {
+    /// Parse "secs" or "secs:aggression", e.g. "1.5" or "1.5:4"; the engine accepts 0.1 to 18 s.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (secs, aggression) = s.split_once(':').unwrap_or((s, "0"));
+        let secs_per_move = secs
+            .parse::<f32>()
+            .ok()
+            .filter(|t| (0.1..18.0).contains(t))?;
+        let aggression = aggression
+            .parse::<i16>()
+            .ok()
+            .filter(|a| (0..=10).contains(a))?;
+        Some(Self {
+            secs_per_move,
+            aggression,
+        })
+    }
+}
+
+impl std::fmt::Display for EngineConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} s/move, aggression {}",
+            self.secs_per_move, self.aggression
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+/// Play one game from the initial position; `players` holds the settings for [white, black].
+fn play_game(g: &mut engine::Game, players: [EngineConfig; 2]) -> GameResult {
+    engine::reset_game(g);
+    while g.move_counter < MAX_PLIES {
+        let turn = engine::turn(g);
+        match engine::game_state(g) {
+            engine::STATE_PLAYING => {}
+            engine::STATE_CHECKMATE if turn == 0 => return GameResult::BlackWins,
+            engine::STATE_CHECKMATE => return GameResult::WhiteWins,
+            _ => return GameResult::Draw,
+        }
+        g.secs_per_move = players[turn].secs_per_move;
+        g.aggression = players[turn].aggression;
+        let mv = engine::reply(g, &AtomicBool::new(false));
+        engine::do_move_promoting(g, mv.src as i8, mv.dst as i8, mv.promotion(), false);
+    }
+    GameResult::Draw
+}
+
+/// Play `games` games between `a` and `b`, alternating colors, and print the score from a's view.
+/// Both configurations share one engine instance, and so one transposition table.
+pub fn run_match(games: u32, a: EngineConfig, b: EngineConfig) {
+    let mut g = engine::new_game();
+    let (mut wins, mut draws, mut losses) = (0, 0, 0);
+    for n in 0..games {
+        let a_is_white = n % 2 == 0;
+        let players = if a_is_white { [a, b] } else { [b, a] };
+        let result = play_game(&mut g, players);
+        let a_won = match result {
+            GameResult::Draw => None,
+            GameResult::WhiteWins => Some(a_is_white),
+            GameResult::BlackWins => Some(!a_is_white),
+        };
+        match a_won {
+            Some(true) => wins += 1,
+            Some(false) => losses += 1,
+            None => draws += 1,
+        }
+        println!(
+            "Game {}: A plays {}, {:?} after {} plies",
+            n + 1,
+            if a_is_white { "white" } else { "black" },
+            result,
+            g.move_counter
+        );
+    }
+    println!("A: {a}");
+    println!("B: {b}");
+    println!("Score for A: +{wins} ={draws} -{losses}");
+}
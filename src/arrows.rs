@@ -0,0 +1,134 @@
+// Arrows drawn over the board: the last move and the user's annotation arrows.
+// The arrows are one SVG in board coordinates; each square shows its own slice of it, so the
+// squares stay clickable buttons.
+
+use std::sync::{Arc, Mutex};
+
+use resvg::{tiny_skia, usvg};
+use xilem::Blob;
+use xilem::peniko::{ImageAlphaType, ImageBrush, ImageData, ImageFormat};
+
+/// Edge length of a rasterized square slice in pixels.
+const IMAGE_SIZE: u32 = 128;
+/// Edge length of a square in SVG units.
+const SQUARE: f32 = 100.0;
+const SHAFT_WIDTH: f32 = 18.0;
+const HEAD_LENGTH: f32 = 40.0;
+const HEAD_WIDTH: f32 = 48.0;
+const LAST_MOVE_COLOR: &str = "#e08020";
+const USER_COLOR: &str = "#20a040";
+const ARROW_OPACITY: f32 = 0.6;
+
+/// Slices of the arrows, indexed by engine square; `None` for squares they do not cross.
+pub type ArrowSlices = Arc<[Option<ImageBrush>; 64]>;
+
+/// Draw position (row, column) of an engine square, row 0 at the top.
+fn draw_pos(square: usize, rotated: bool) -> (usize, usize) {
+    let (row, col) = (square / 8, square % 8);
+    if rotated {
+        (row, col)
+    } else {
+        (7 - row, 7 - col)
+    }
+}
+
+fn center(square: usize, rotated: bool) -> (f32, f32) {
+    let (row, col) = draw_pos(square, rotated);
+    ((col as f32 + 0.5) * SQUARE, (row as f32 + 0.5) * SQUARE)
+}
+
+/// SVG elements of an arrow between the centers of two squares.
+fn arrow_svg(from: usize, to: usize, color: &str, rotated: bool) -> String {
+    let (x0, y0) = center(from, rotated);
+    let (x1, y1) = center(to, rotated);
+    let len = ((x1 - x0).powi(2) + (y1 - y0).powi(2)).sqrt();
+    let (dx, dy) = ((x1 - x0) / len, (y1 - y0) / len);
+    // The shaft ends where the head begins.
+    let (bx, by) = (x1 - dx * HEAD_LENGTH, y1 - dy * HEAD_LENGTH);
+    let (nx, ny) = (-dy * HEAD_WIDTH / 2.0, dx * HEAD_WIDTH / 2.0);
+    format!(
+        r#"<g opacity="{ARROW_OPACITY}" fill="{color}" stroke="{color}">
+<line x1="{x0}" y1="{y0}" x2="{bx}" y2="{by}" stroke-width="{SHAFT_WIDTH}"/>
+<polygon points="{x1},{y1} {},{} {},{}" stroke="none"/>
+</g>
+"#,
+        bx + nx,
+        by + ny,
+        bx - nx,
+        by - ny,
+    )
+}
+
+/// The part of `tree` on the square at draw position (row, col), if anything is drawn there.
+fn slice(tree: &usvg::Tree, row: usize, col: usize) -> Option<ImageBrush> {
+    let mut pixmap = tiny_skia::Pixmap::new(IMAGE_SIZE, IMAGE_SIZE)?;
+    let scale = IMAGE_SIZE as f32 / SQUARE;
+    let transform =
+        tiny_skia::Transform::from_translate(-(col as f32) * SQUARE, -(row as f32) * SQUARE)
+            .post_scale(scale, scale);
+    resvg::render(tree, transform, &mut pixmap.as_mut());
+    if pixmap.pixels().iter().all(|p| p.alpha() == 0) {
+        return None;
+    }
+    let data = ImageData {
+        data: Blob::new(Arc::new(pixmap.take())),
+        format: ImageFormat::Rgba8,
+        alpha_type: ImageAlphaType::AlphaPremultiplied,
+        width: IMAGE_SIZE,
+        height: IMAGE_SIZE,
+    };
+    Some(ImageBrush::new(data))
+}
+
+fn render(key: &Key) -> ArrowSlices {
+    let (last_move, user, rotated) = key;
+    let board = 8.0 * SQUARE;
+    let mut svg =
+        format!(r#"<svg xmlns="http://www.w3.org/2000/svg" width="{board}" height="{board}">"#);
+    if let Some((from, to)) = *last_move {
+        svg.push_str(&arrow_svg(from, to, LAST_MOVE_COLOR, *rotated));
+    }
+    for &(from, to) in user {
+        svg.push_str(&arrow_svg(from, to, USER_COLOR, *rotated));
+    }
+    svg.push_str("</svg>");
+
+    let mut slices: [Option<ImageBrush>; 64] = std::array::from_fn(|_| None);
+    let Ok(tree) = usvg::Tree::from_str(&svg, &usvg::Options::default()) else {
+        return Arc::new(slices);
+    };
+    for (square, slot) in slices.iter_mut().enumerate() {
+        let (row, col) = draw_pos(square, *rotated);
+        *slot = slice(&tree, row, col);
+    }
+    Arc::new(slices)
+}
+
+/// The arrows drawn, as (from, to) engine squares, and the board orientation.
+type Key = (Option<(usize, usize)>, Vec<(usize, usize)>, bool);
+
+/// Slices of the last move arrow and the user's arrows for the board orientation
+/// `rotated`, or `None` without arrows. The last set is cached, as the board is redrawn
+/// on every tick.
+pub fn arrows(
+    last_move: Option<(usize, usize)>,
+    user: &[(usize, usize)],
+    rotated: bool,
+) -> Option<ArrowSlices> {
+    static CACHE: Mutex<Option<(Key, ArrowSlices)>> = Mutex::new(None);
+    if last_move.is_none() && user.is_empty() {
+        return None;
+    }
+    let mut cache = CACHE.lock().unwrap();
+    if let Some((key, slices)) = &*cache
+        && key.0 == last_move
+        && key.1 == user
+        && key.2 == rotated
+    {
+        return Some(slices.clone());
+    }
+    let key = (last_move, user.to_vec(), rotated);
+    let slices = render(&key);
+    *cache = Some((key, slices.clone()));
+    Some(slices)
+}
@@ -0,0 +1,61 @@
+// Sound effects for moves, synthesized with rodio; silent if there is no audio device.
+
+use std::sync::{OnceLock, mpsc};
+use std::thread;
+use std::time::Duration;
+
+use rodio::source::{SineWave, Source};
+use rodio::{OutputStream, Sink};
+
+/// Loudness of the tones, 1.0 being full scale.
+const VOLUME: f32 = 0.2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sound {
+    Move,
+    Capture,
+    Castling,
+    Check,
+}
+
+/// The tones of a sound, as frequency (Hz) and length (ms).
+fn tones(sound: Sound) -> &'static [(f32, u64)] {
+    match sound {
+        Sound::Move => &[(660.0, 60)],
+        Sound::Capture => &[(440.0, 50), (330.0, 90)],
+        Sound::Castling => &[(660.0, 50), (880.0, 50)],
+        Sound::Check => &[(880.0, 80), (1175.0, 120)],
+    }
+}
+
+/// The output stream can't leave the thread that opened it, so one thread plays all sounds.
+fn player() -> &'static mpsc::Sender<Sound> {
+    static PLAYER: OnceLock<mpsc::Sender<Sound>> = OnceLock::new();
+    PLAYER.get_or_init(|| {
+        let (tx, rx) = mpsc::channel::<Sound>();
+        thread::spawn(move || {
+            let Ok((_stream, handle)) = OutputStream::try_default() else {
+                return;
+            };
+            for sound in rx {
+                let Ok(sink) = Sink::try_new(&handle) else {
+                    continue;
+                };
+                for &(frequency, millis) in tones(sound) {
+                    sink.append(
+                        SineWave::new(frequency)
+                            .take_duration(Duration::from_millis(millis))
+                            .amplify(VOLUME),
+                    );
+                }
+                sink.detach();
+            }
+        });
+        tx
+    })
+}
+
+/// Start playing `sound` and return at once.
+pub fn play(sound: Sound) {
+    let _ = player().send(sound);
+}
@@ -0,0 +1,44 @@
+// Copying text to the system clipboard, using the platform's command line tools.
+// Linux: wl-copy, xclip or xsel; macOS: pbcopy; Windows: clip.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn clipboard_commands() -> &'static [&'static [&'static str]] {
+    if cfg!(target_os = "macos") {
+        &[&["pbcopy"]]
+    } else if cfg!(target_os = "windows") {
+        &[&["clip"]]
+    } else {
+        &[
+            &["wl-copy"],
+            &["xclip", "-selection", "clipboard"],
+            &["xsel", "--clipboard", "--input"],
+        ]
+    }
+}
+
+fn copy_with(cmd: &[&str], text: &str) -> std::io::Result<bool> {
+    let mut child = Command::new(cmd[0])
+        .args(&cmd[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    Ok(child.wait()?.success())
+}
+
+/// Put `text` on the clipboard; the error names the tools that were tried.
+pub fn copy(text: &str) -> Result<(), String> {
+    let commands = clipboard_commands();
+    for cmd in commands {
+        if let Ok(true) = copy_with(cmd, text) {
+            return Ok(());
+        }
+    }
+    let tried: Vec<_> = commands.iter().map(|cmd| cmd[0]).collect();
+    Err(format!("no clipboard tool found ({})", tried.join(", ")))
+}
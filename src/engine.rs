@@ -13,7 +13,7 @@
 // create a real GUI: well, at least we have an egui version with threading now
 // avoid global variables, make board a parameter of abeta() // Done in Rust port
 // make transposition table size configurable?
-// make aggression configurable
+// make aggression configurable // Done, see Game::aggression
 // make aggression depending on winning/loosing
 // add optional random noise
 // and of course much more: setting up a position, saving/loading games, undo, opening lib, ...
@@ -28,8 +28,10 @@ use core::ops::Range;
 use num_traits::sign::signum;
 use std::cmp::max;
 use std::collections::HashMap;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
 use std::time::{Duration, Instant};
 
 // ### our own primitive bitset type
@@ -64,7 +66,7 @@ impl BitSet {
     }
     */
 
-    fn _remove<T>(&mut self, index: T)
+    fn remove<T>(&mut self, index: T)
     where
         u64: std::ops::Shl<T, Output = u64>,
     {
@@ -133,6 +135,7 @@ pub struct Game {
     table_col: i64,
     max_cup: i64,
     ab_call: i64,
+    nodes: u64, // abeta() calls of the running reply(), unlike ab_call counted in all builds
     score_hash_succ: i64,
     floor_hash_succ: i64,
     hash_succ: i64,
@@ -156,13 +159,24 @@ pub struct Game {
     king_path: Path8,
     to_100: u8,
     pub secs_per_move: f32,
+    pub max_depth: usize, // search exactly this deep without time limits; 0 = use secs_per_move
+    pub aggression: i16,  // 0 = neutral; larger values favor attacks for the side the engine plays
+    root_color: Color,
     time_0: std::time::Duration,
     _time_1: std::time::Duration,
     time_2: std::time::Duration,
     time_3: std::time::Duration,
     time_4: std::time::Duration,
-    pub move_counter: u16,
+    pub move_counter: u16, // plies played from the initial position; even when white is to move
     pjm: i8,
+    pub search_info: Option<mpsc::Sender<SearchInfo>>, // receives the best move after each depth
+}
+
+// best move of a running search, sent after each completed iteration
+#[derive(Clone, Copy, Debug)]
+pub struct SearchInfo {
+    pub src: i64,
+    pub dst: i64,
 }
 
 pub fn print_move_list(g: &Game) {
@@ -202,6 +216,7 @@ pub fn reset_game(g: &mut Game) {
     g.move_chain = [0; 64]; // which is better/faster?
     // g.move_chain.iter_mut().for_each(|m| *m = 0)
     g.move_counter = 0;
+    g.to_100 = 0;
     g.pjm = -1;
     g.has_moved = BitSet::new();
 }
@@ -224,9 +239,22 @@ pub fn new_game() -> Game {
         println!("salewskiChessDebug2");
     }
 
+    game_with_table(TTE_SIZE)
+}
+
+// like new_game(), but with a transposition table of only SMALL_TTE_SIZE entries, which
+// is quickly allocated; enough for replaying moves and for the shallow move_loss() search
+pub fn new_position() -> Game {
+    game_with_table(SMALL_TTE_SIZE)
+}
+
+fn game_with_table(tt_size: usize) -> Game {
     // Default::default() does not work, e.g. Duration has no default value!
     let mut g = Game {
         secs_per_move: 1.5,
+        max_depth: 0,
+        aggression: 0,
+        root_color: COLOR_WHITE,
         time_0: Duration::new(0, 0),
         _time_1: Duration::new(0, 0),
         time_2: Duration::new(0, 0),
@@ -236,6 +264,7 @@ pub fn new_game() -> Game {
         table_col: 0,
         max_cup: 0,
         ab_call: 0,
+        nodes: 0,
         score_hash_succ: 0,
         floor_hash_succ: 0,
         hash_succ: 0,
@@ -245,7 +274,7 @@ pub fn new_game() -> Game {
         max_delta_len: 0,
         is_endgame: false,
         start_time: Instant::now(),
-        tt: vec![Default::default(); TTE_SIZE],
+        tt: vec![Default::default(); tt_size],
         debug_list: Vec::new(),
         history: HashMap::new(),
         board: SETUP,
@@ -275,6 +304,7 @@ pub fn new_game() -> Game {
         to_100: 0,
         move_counter: 0,
         pjm: -1,
+        search_info: None,
     };
     init_pawn(&mut g, COLOR_WHITE);
     init_pawn(&mut g, COLOR_BLACK);
@@ -423,6 +453,8 @@ type BitBuffer192 = [u8; bit_buffer_size()];
 
 const MAX_DEPTH: usize = 15; // other values should work as well
 
+static NO_STOP: AtomicBool = AtomicBool::new(false); // for searches that can't be stopped
+
 const VOID_ID: i8 = 0;
 const PAWN_ID: i8 = 1;
 const KNIGHT_ID: i8 = 2;
@@ -598,8 +630,8 @@ const BETH: i64 = i16::MIN as i64;
 const NO_NXT_DIR_IDX: u8 = 100;
 
 pub type State = i32;
-const STATE_PLAYING: i32 = 0;
-const STATE_STALEMATE: i32 = 1;
+pub const STATE_PLAYING: i32 = 0;
+pub const STATE_STALEMATE: i32 = 1;
 pub const STATE_CHECKMATE: i32 = 2;
 const STATE_NO_VALID_MOVE: i32 = 3;
 const STATE_CAN_CAPTURE_KING: i32 = 4;
@@ -688,6 +720,7 @@ fn lift(a: &mut i64, b: i64) {
 }
 
 const TTE_SIZE: usize = 1024 * 1024 * 2; // must be a power of 2
+const SMALL_TTE_SIZE: usize = 1024; // for new_position(), a power of 2 as well
 const TT_TRY: usize = 5;
 
 fn odd(i: i8) -> bool {
@@ -777,10 +810,10 @@ fn bit_buffer_hash(key: &BitBuffer192) -> usize {
 
 // we use position 0, which is actually a valid index, as marker for failure, avoiding -1 with isize type.
 fn get_tte(g: &mut Game, key: BitBuffer192) -> usize {
-    debug_assert!(g.tt.len() == TTE_SIZE);
+    debug_assert!(g.tt.len().is_power_of_two());
     let h0 = bit_buffer_hash(&key);
     for i in 0..(TT_TRY + 1) {
-        let h = (h0.wrapping_add(i)) & (TTE_SIZE - 1);
+        let h = (h0.wrapping_add(i)) & (g.tt.len() - 1);
         if g.tt[h].key[0..CORE_BIT_BUFFER_SIZE] == key[0..CORE_BIT_BUFFER_SIZE] {
             if BIT_BUFFER_SIZE == HASH_BIT_BUFFER_SIZE {
                 let _bh = board_hash(g.board).to_le_bytes();
@@ -800,7 +833,7 @@ fn debug_inc(x: &mut i64) {
 }
 
 fn put_tte(g: &mut Game, key: BitBuffer192, mut res: HashResult, pri: i64, hash_pos: usize) {
-    debug_assert!(g.tt.len() == TTE_SIZE);
+    debug_assert!(g.tt.len().is_power_of_two());
     debug_inc(&mut g.table_put);
     if hash_pos > 0 && res.pri < pri {
         res.pri = pri;
@@ -809,7 +842,7 @@ fn put_tte(g: &mut Game, key: BitBuffer192, mut res: HashResult, pri: i64, hash_
     }
     let h0 = bit_buffer_hash(&key);
     for i in 0..(TT_TRY + 1) {
-        let h = (h0.wrapping_add(i)) & (TTE_SIZE - 1);
+        let h = (h0.wrapping_add(i)) & (g.tt.len() - 1);
         if g.tt[h].res.pri < pri {
             res.pri = pri;
             g.tt[h].res = res;
@@ -1348,8 +1381,21 @@ pub struct Move {
     pub checkmate_in: i8,
     //only_one_move: bool,
     control: ChessSquares,
-    promote_to: i64,
+    pub promote_to: i64, // signed figure a pawn promotes to, 0 for other moves
     pub state: State,
+    pub depth: i32, // depth of the completed search iteration the move comes from
+    pub nodes: u64, // positions searched by reply() for this move
+}
+
+impl Move {
+    // absolute figure ID for do_move_promoting(); queen unless the search chose an underpromotion
+    pub fn promotion(&self) -> FigureID {
+        if self.promote_to == 0 {
+            QUEEN_ID
+        } else {
+            self.promote_to.abs() as FigureID
+        }
+    }
 }
 
 // result is for White
@@ -1479,6 +1525,21 @@ fn in_check(g: &Game, si: i8, col: Color, check_king_attack: bool) -> bool {
     false
 }
 
+// bonus for attacked opponent pieces and for moves into the 3x3 zone around the opponent king
+fn aggression_bonus(g: &Game, kks: &KKS, color: Color) -> i64 {
+    let opp_king = king_pos(g, opp_color(color));
+    let mut bonus: i64 = 0;
+    for el in kks {
+        if el.df != VOID_ID {
+            bonus += el.df.abs() as i64 / 2 + 1;
+        }
+        if (row(el.di) - row(opp_king)).abs() <= 1 && (col(el.di) - col(opp_king)).abs() <= 1 {
+            bonus += 1;
+        }
+    }
+    bonus * g.aggression as i64
+}
+
 fn king_pos(g: &Game, c: Color) -> i8 {
     let k = KING_ID * c as i8;
     for (i, f) in g.board.iter().enumerate() {
@@ -1514,6 +1575,7 @@ const NO_EXTEND_AT_ALL: bool = false; // avoid depth extends for now
 // alpha_0, beta: the search window for prunning
 // ep_pos: if not -1, it indicates the position of the en pasant square
 // for en passant capture, i.e. after pawn move e2 e4 ep_pos is e3.
+// stop: when set, the search is abandoned like at the hard time limit
 // Result: Currently we return a value object. We may change that to a reference type, that
 // would allow changing moves and displaying whole move sequences. Maybe a bit slower.
 //
@@ -1526,17 +1588,19 @@ fn abeta(
     beta: i64,
     old_list_len: i64,
     ep_pos: i8,
+    stop: &AtomicBool,
 ) -> Move {
     let mut result = Move {
         state: STATE_NO_VALID_MOVE,
         score: LOWEST_SCORE,
         ..Default::default()
     };
-    if g.start_time.elapsed() > g.time_4 {
-        return result; // invalid due to hard time contraints.
+    if g.start_time.elapsed() > g.time_4 || stop.load(Ordering::Relaxed) {
+        return result; // invalid due to hard time contraints or a stop request.
     }
     debug_assert!(alpha_0 < beta);
     debug_inc(&mut g.ab_call);
+    g.nodes += 1;
     debug_assert!(MAX_DEPTH == 15);
     debug_assert!(V_RATIO == 8);
     let depth_0: usize = max(v_depth / V_RATIO, 0) as usize; // starting at depth_0 == 0 we do only captures
@@ -1729,8 +1793,12 @@ fn abeta(
         // this field is optional information
         hash_res.tested_for_check = true;
     }
-    let hash_res_kks_len =
+    let mut hash_res_kks_len =
         ev_board(&hash_res.kks, pop) + attacs + hash_res.control.0.count_ones() as i64;
+    if g.aggression > 0 && color == g.root_color {
+        // only the engine's own attacks are rewarded, which makes its play sharper
+        hash_res_kks_len += aggression_bonus(g, &hash_res.kks, color);
+    }
     if depth_0 == 0 {
         // more detailed null move estimation for quiescence search. NOTE: Take attacs into account?
         evaluation += hash_res_kks_len; // we may do a more fine grained board control evaluation?
@@ -1963,7 +2031,7 @@ fn abeta(
                 // test for castlings as well?
                 g.to_100 = 0;
             } else {
-                g.to_100 += 1;
+                g.to_100 = g.to_100.saturating_add(1);
             }
             m = abeta(
                 g,
@@ -1977,6 +2045,7 @@ fn abeta(
                 -alpha,
                 hash_res_kks_len,
                 nep_pos,
+                stop,
             );
             if m.score != LOWEST_SCORE {
                 // not a hard cut with invalid result
@@ -2142,11 +2211,16 @@ fn close_to_checkmate(score: i64) -> bool {
     score > 17000 || score < -17000
 }
 
-fn alphabeta(g: &mut Game, color: Color, depth: i64, ep_pos: i8) -> Move {
+fn alphabeta(g: &mut Game, color: Color, depth: i64, ep_pos: i8, stop: &AtomicBool) -> Move {
     debug_assert!((0.1..18.0).contains(&g.secs_per_move));
     //g.time_0 = Duration::from_secs_f32(g.secs_per_move * 0.7);
-    g.time_2 = Duration::from_secs_f32(g.secs_per_move * 1.5);
-    g.time_3 = Duration::from_secs_f32(g.secs_per_move * 2.5);
+    if g.max_depth > 0 {
+        g.time_2 = Duration::MAX;
+        g.time_3 = Duration::MAX;
+    } else {
+        g.time_2 = Duration::from_secs_f32(g.secs_per_move * 1.5);
+        g.time_3 = Duration::from_secs_f32(g.secs_per_move * 2.5);
+    }
     //g.time_4 = Duration::from_secs_f32(g.secs_per_move * 5.0);
     g.start_time = Instant::now();
     reset_statistics(g);
@@ -2159,6 +2233,7 @@ fn alphabeta(g: &mut Game, color: Color, depth: i64, ep_pos: i8) -> Move {
         AB_INF as i64,
         0, // does not matter for topmost ply
         ep_pos,
+        stop,
     );
     if close_to_checkmate(result.score) {
         result.checkmate_in = depth as i8;
@@ -2178,8 +2253,30 @@ const FLAG_CAPTURE: i32 = 1;
 const FLAG_EP: i32 = 2;
 const FLAG_PROMOTION: i32 = 3;
 const FLAG_PROCAP: i32 = 4;
+pub const FLAG_CASTLING: i32 = 5;
 
 pub fn do_move(g: &mut Game, p0: Position, p1: Position, silent: bool) -> i32 {
+    do_move_promoting(g, p0, p1, QUEEN_ID, silent)
+}
+
+// source and destination of the rook, when the king castles from p0 to p1
+pub fn castling_rook(p0: Position, p1: Position) -> (Position, Position) {
+    if col(p1) == 1 {
+        (p0 - 3, p0 - 1)
+    } else {
+        (p0 + 4, p0 + 1)
+    }
+}
+
+// like do_move(), but a pawn reaching the base row becomes figure `promote_to` (absolute ID, 2..=5)
+pub fn do_move_promoting(
+    g: &mut Game,
+    p0: Position,
+    p1: Position,
+    promote_to: FigureID,
+    silent: bool,
+) -> i32 {
+    debug_assert!((KNIGHT_ID..=QUEEN_ID).contains(&promote_to));
     p(g.board);
     let mut result: i32 = 0;
     if !is_void_at(g, p1) {
@@ -2195,19 +2292,16 @@ pub fn do_move(g: &mut Game, p0: Position, p1: Position, silent: bool) -> i32 {
             // test for castlings as well?
             g.to_100 = 0;
         } else {
-            g.to_100 += 1;
+            g.to_100 = g.to_100.saturating_add(1);
         }
     }
     if (p1 - p0).abs() == 2 && is_a_king_at(g, p0) {
-        if col(p1) == 1 {
-            g.board[p0 as usize - 1] = g.board[p0 as usize - 3];
-            g.board[p0 as usize - 3] = VOID_ID;
-        } else {
-            g.board[p0 as usize + 1] = g.board[p0 as usize + 4];
-            g.board[p0 as usize + 4] = VOID_ID;
-        }
+        let (r0, r1) = castling_rook(p0, p1);
+        g.board[r1 as usize] = g.board[r0 as usize];
+        g.board[r0 as usize] = VOID_ID;
+        result = FLAG_CASTLING;
     } else if base_row(p1) && is_a_pawn_at(g, p0) {
-        g.board[p0 as usize] *= QUEEN_ID;
+        g.board[p0 as usize] *= promote_to;
         result = if result == FLAG_CAPTURE {
             FLAG_PROCAP
         } else {
@@ -2306,8 +2400,417 @@ pub fn tag(g: &mut Game, si: i64) -> KKS {
     s
 }
 
+// mark all squares reached along the sliding paths, stopping each direction at the first piece
+fn mark_slides(g: &Game, path: &[Gnu; 16], sign: i8, map: &mut [i8; 64]) {
+    let mut i = 0;
+    while path[i].pos >= 0 {
+        let di = path[i].pos as usize;
+        map[di] += sign;
+        if g.board[di] == VOID_ID {
+            i += 1;
+        } else {
+            i = path[i].nxt_dir_idx as usize;
+        }
+    }
+}
+
+// net control per square: number of white minus number of black pieces attacking (or defending) it
+pub fn control_map(g: &Game) -> [i8; 64] {
+    let mut map = [0; 64];
+    for (si, &f) in g.board.iter().enumerate() {
+        let sign = signum(f);
+        match f.abs() {
+            PAWN_ID => {
+                for p in &g.pawn_path[col_idx(sign as Color) as usize][si][0..2] {
+                    if p.pos >= 0 {
+                        map[p.pos as usize] += sign;
+                    }
+                }
+            }
+            KNIGHT_ID | KING_ID => {
+                let path = if f.abs() == KNIGHT_ID {
+                    &g.knight_path[si]
+                } else {
+                    &g.king_path[si]
+                };
+                for p in path.iter().take_while(|p| p.pos >= 0) {
+                    map[p.pos as usize] += sign;
+                }
+            }
+            BISHOP_ID => mark_slides(g, &g.bishop_path[si], sign, &mut map),
+            ROOK_ID => mark_slides(g, &g.rook_path[si], sign, &mut map),
+            QUEEN_ID => {
+                mark_slides(g, &g.bishop_path[si], sign, &mut map);
+                mark_slides(g, &g.rook_path[si], sign, &mut map);
+            }
+            _ => {}
+        }
+    }
+    map
+}
+
+// side to move, 0 = white, 1 = black; the single source for turn decisions
+pub fn turn(g: &Game) -> usize {
+    g.move_counter as usize % 2
+}
+
+// color of the side to move
+fn side_to_move(g: &Game) -> Color {
+    if turn(g) == 0 {
+        COLOR_WHITE
+    } else {
+        COLOR_BLACK
+    }
+}
+
+// static evaluation of the current position in centipawns, positive when White is better
+pub fn evaluate(g: &Game) -> i64 {
+    plain_evaluate_board(g)
+}
+
+// square of the king of the side to move if it is in check
+pub fn king_in_check(g: &Game) -> Option<Position> {
+    let color = side_to_move(g);
+    let k = king_pos(g, color);
+    in_check(g, k, color, true).then_some(k)
+}
+
+// full move number as in FEN, starting at 1 and incremented after each black move
+pub fn fullmove_number(g: &Game) -> u16 {
+    g.move_counter / 2 + 1
+}
+
+// plies since the last capture or pawn move (FEN halfmove clock)
+pub fn halfmove_clock(g: &Game) -> u8 {
+    g.to_100
+}
+
+// FEN of the current position; castling rights are derived from unmoved kings and rooks,
+// the en passant square is given after every double pawn step
+pub fn to_fen(g: &Game) -> String {
+    let mut fen = String::new();
+    for row in (0..8).rev() {
+        let mut empty = 0;
+        for col in (0..8).rev() {
+            let f = g.board[row * 8 + col];
+            if f == VOID_ID {
+                empty += 1;
+                continue;
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+                empty = 0;
+            }
+            let c = b" pnbrqk"[f.unsigned_abs() as usize] as char;
+            fen.push(if f > 0 { c.to_ascii_uppercase() } else { c });
+        }
+        if empty > 0 {
+            fen.push_str(&empty.to_string());
+        }
+        if row > 0 {
+            fen.push('/');
+        }
+    }
+    fen.push_str(if turn(g) == 0 { " w " } else { " b " });
+    let unmoved = |pos: usize, f: FigureID| g.board[pos] == f && !g.has_moved.contains(pos);
+    let mut castling = String::new();
+    if unmoved(WK3, KING_ID) {
+        if unmoved(WR0, ROOK_ID) {
+            castling.push('K');
+        }
+        if unmoved(WR7, ROOK_ID) {
+            castling.push('Q');
+        }
+    }
+    if unmoved(BK59, -KING_ID) {
+        if unmoved(BR56, -ROOK_ID) {
+            castling.push('k');
+        }
+        if unmoved(BR63, -ROOK_ID) {
+            castling.push('q');
+        }
+    }
+    if castling.is_empty() {
+        castling.push('-');
+    }
+    fen.push_str(&castling);
+    fen.push(' ');
+    if g.pjm >= 0 {
+        fen.push((b'h' - col(g.pjm) as u8) as char);
+        fen.push((b'1' + row(g.pjm) as u8) as char);
+    } else {
+        fen.push('-');
+    }
+    fen.push_str(&format!(" {} {}", halfmove_clock(g), fullmove_number(g)));
+    fen
+}
+
+// reason why from_fen rejected a FEN string
+#[derive(Clone, Debug)]
+pub struct FenError(String);
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid FEN: {}", self.0)
+    }
+}
+
+// a game starting from the position given as FEN; the halfmove clock and fullmove number
+// may be omitted. Castling rights become unmoved kings and rooks, all other pieces count
+// as moved.
+pub fn from_fen(fen: &str) -> Result<Game, FenError> {
+    let mut g = new_game();
+    set_fen(&mut g, fen)?;
+    Ok(g)
+}
+
+// like from_fen(), but sets up the position in `g`, keeping its transposition table; after
+// an error, `g` holds its previous position or the initial one
+pub fn set_fen(g: &mut Game, fen: &str) -> Result<(), FenError> {
+    let err = |msg: &str| Err(FenError(msg.into()));
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    if !(4..=6).contains(&fields.len()) {
+        return err("expected 4 to 6 fields");
+    }
+    let mut board: Board = [VOID_ID; 64];
+    let ranks: Vec<&str> = fields[0].split('/').collect();
+    if ranks.len() != 8 {
+        return err("expected 8 ranks");
+    }
+    for (i, rank) in ranks.iter().enumerate() {
+        let row = 7 - i;
+        let mut file = 0;
+        for c in rank.chars() {
+            if let Some(n) = c.to_digit(10).filter(|n| (1..=8).contains(n)) {
+                file += n as usize;
+                continue;
+            }
+            let Some(id) = " pnbrqk".find(c.to_ascii_lowercase()).filter(|&id| id > 0) else {
+                return Err(FenError(format!("unknown piece '{c}'")));
+            };
+            if file >= 8 {
+                return Err(FenError(format!(
+                    "rank {} has more than 8 squares",
+                    row + 1
+                )));
+            }
+            let id = id as FigureID;
+            board[row * 8 + 7 - file] = if c.is_ascii_uppercase() { id } else { -id };
+            file += 1;
+        }
+        if file != 8 {
+            return Err(FenError(format!(
+                "rank {} does not have 8 squares",
+                row + 1
+            )));
+        }
+    }
+    for color in [1, -1] {
+        if board.iter().filter(|&&f| f == color * KING_ID).count() != 1 {
+            return err("each side needs exactly one king");
+        }
+    }
+    if board[..8]
+        .iter()
+        .chain(&board[56..])
+        .any(|f| f.abs() == PAWN_ID)
+    {
+        return err("pawn on the first or eighth rank");
+    }
+    let black_to_move = match fields[1] {
+        "w" => false,
+        "b" => true,
+        _ => return err("side to move must be 'w' or 'b'"),
+    };
+    let mut has_moved = BitSet(u64::MAX);
+    if fields[2] != "-" {
+        for c in fields[2].chars() {
+            let (k, r, f) = match c {
+                'K' => (WK3, WR0, KING_ID),
+                'Q' => (WK3, WR7, KING_ID),
+                'k' => (BK59, BR56, -KING_ID),
+                'q' => (BK59, BR63, -KING_ID),
+                _ => return Err(FenError(format!("unknown castling right '{c}'"))),
+            };
+            if board[k] != f || board[r] != f.signum() * ROOK_ID {
+                return Err(FenError(format!(
+                    "castling right '{c}' without king and rook"
+                )));
+            }
+            has_moved.remove(k);
+            has_moved.remove(r);
+        }
+    }
+    let pjm = match fields[3].as_bytes() {
+        b"-" => -1,
+        &[file @ b'a'..=b'h', rank] if rank == if black_to_move { b'3' } else { b'6' } => {
+            (rank - b'1') as i8 * 8 + (b'h' - file) as i8
+        }
+        _ => return err("bad en passant square"),
+    };
+    // past 100 halfmoves the game is drawn already, so larger clocks are not real positions
+    let to_100 = match fields.get(4) {
+        Some(s) => s
+            .parse()
+            .ok()
+            .filter(|&n| n <= 100)
+            .ok_or_else(|| FenError("bad halfmove clock".into()))?,
+        None => 0,
+    };
+    let fullmove: u16 = match fields.get(5) {
+        Some(s) => s
+            .parse()
+            .ok()
+            .filter(|&n| n > 0)
+            .ok_or_else(|| FenError("bad fullmove number".into()))?,
+        None => 1,
+    };
+    let Some(move_counter) = (fullmove - 1)
+        .checked_mul(2)
+        .and_then(|n| n.checked_add(black_to_move as u16))
+    else {
+        return err("fullmove number too large");
+    };
+    reset_game(g);
+    g.board = board;
+    g.has_moved = has_moved;
+    g.move_counter = move_counter;
+    g.to_100 = to_100;
+    g.pjm = pjm;
+    let waiting = -side_to_move(g);
+    if in_check(g, king_pos(g, waiting), waiting, true) {
+        reset_game(g);
+        return err("the side not to move is in check");
+    }
+    Ok(())
+}
+
+// true if neither side can mate: kings with at most one knight or bishop, or only
+// bishops that all stand on squares of the same color
+pub fn is_insufficient_material(g: &Game) -> bool {
+    let mut minors = Vec::new();
+    for (i, &f) in g.board.iter().enumerate() {
+        match f.abs() {
+            VOID_ID | KING_ID => {}
+            KNIGHT_ID | BISHOP_ID => {
+                minors.push((f.abs(), (row(i as Position) + col(i as Position)) % 2))
+            }
+            _ => return false,
+        }
+    }
+    minors.len() <= 1
+        || minors
+            .iter()
+            .all(|&(f, shade)| f == BISHOP_ID && shade == minors[0].1)
+}
+
+// key of the position for repetition checks: piece placement, side to move, castling
+// rights and en passant square, as in the first four FEN fields; the en passant square
+// only counts if a pawn can legally capture there
+pub fn position_key(g: &mut Game) -> u64 {
+    let en_passant = g.pjm >= 0
+        && legal_moves(g)
+            .iter()
+            .any(|&(si, di)| di == g.pjm && is_a_pawn_at(g, si));
+    let mut hasher = DefaultHasher::new();
+    for (i, field) in to_fen(g).split(' ').take(4).enumerate() {
+        if i == 3 && !en_passant { "-" } else { field }.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+// legal moves of the side to move as (source, destination)
+pub fn legal_moves(g: &mut Game) -> Vec<(Position, Position)> {
+    let color = side_to_move(g);
+    let mut moves = Vec::new();
+    for si in POS_RANGE {
+        if signum(g.board[si as usize]) as Color == color {
+            moves.extend(tag(g, si as i64).iter().map(|kk| (kk.si, kk.di)));
+        }
+    }
+    moves
+}
+
+// number of legal moves of the side to move
+pub fn legal_move_count(g: &mut Game) -> usize {
+    legal_moves(g).len()
+}
+
+// STATE_CHECKMATE or STATE_STALEMATE when the side to move has no legal move, STATE_PLAYING otherwise
+pub fn game_state(g: &mut Game) -> State {
+    if legal_move_count(g) > 0 {
+        return STATE_PLAYING;
+    }
+    let color = side_to_move(g);
+    if in_check(g, king_pos(g, color), color, true) {
+        STATE_CHECKMATE
+    } else {
+        STATE_STALEMATE
+    }
+}
+
+const BLUNDER_CHECK_DEPTH: i64 = 3;
+
+// centipawns the side to move loses by playing si -> di instead of the best move,
+// from two shallow fixed-depth searches; the game itself is left unchanged
+pub fn move_loss(g: &mut Game, si: Position, di: Position) -> i64 {
+    let color = side_to_move(g);
+    let (board, aggression, root_color) = (g.board, g.aggression, g.root_color);
+    g.aggression = 0;
+    g.time_4 = Duration::MAX;
+    g.root_color = color;
+    let best = alphabeta(g, color, BLUNDER_CHECK_DEPTH, g.pjm, &NO_STOP).score;
+    let loss = loss_against(g, color, best, si, di);
+    g.board = board;
+    g.aggression = aggression;
+    g.root_color = root_color;
+    loss
+}
+
+// loss of si -> di against the score `best` of the best move; changes g.board and g.root_color
+fn loss_against(g: &mut Game, color: Color, best: i64, si: Position, di: Position) -> i64 {
+    let ep_pos = if is_a_pawn_at(g, si) && (si - di).abs() == 16 {
+        (si + di) / 2
+    } else {
+        -1
+    };
+    do_move(g, si, di, true);
+    g.root_color = -color;
+    let reply = alphabeta(g, -color, BLUNDER_CHECK_DEPTH - 1, ep_pos, &NO_STOP);
+    if reply.score == LOWEST_SCORE {
+        return 0; // no legal reply: the move mates or stalemates
+    }
+    (best + reply.score).max(0)
+}
+
+// a random legal move losing at most `margin` centipawns against the best one,
+// judged by the same shallow searches as move_loss(); the game itself is left unchanged
+pub fn random_good_move(g: &mut Game, margin: i64) -> Option<(Position, Position)> {
+    let color = side_to_move(g);
+    let (board, aggression, root_color) = (g.board, g.aggression, g.root_color);
+    g.aggression = 0;
+    g.time_4 = Duration::MAX;
+    g.root_color = color;
+    let best = alphabeta(g, color, BLUNDER_CHECK_DEPTH, g.pjm, &NO_STOP).score;
+    let mut good = Vec::new();
+    for (si, di) in legal_moves(g) {
+        if loss_against(g, color, best, si, di) <= margin {
+            good.push((si, di));
+        }
+        g.board = board;
+        g.root_color = color;
+    }
+    g.aggression = aggression;
+    g.root_color = root_color;
+    if good.is_empty() {
+        return None;
+    }
+    let random = RandomState::new().build_hasher().finish();
+    Some(good[(random % good.len() as u64) as usize])
+}
+
 pub fn move_is_valid2(g: &mut Game, si: i64, di: i64) -> bool {
-    let next = -(g.move_counter as Color % 2) * 2 + 1;
+    let next = side_to_move(g);
     signum(g.board[si as usize]) as Color == next && tag(g, si).iter().any(|&it| it.di == di as i8)
 }
 
@@ -2333,7 +2836,13 @@ pub fn move_to_str(g: &Game, si: Position, di: Position, flag: i32) -> String {
         if g.board[di as usize].abs() == KING_ID && (di - si).abs() == 2 {
             result = String::from(if col(di) == 1 { "o-o" } else { "o-o-o" });
         } else {
-            result = String::from(FIG_STR[g.board[di as usize].unsigned_abs() as usize]);
+            let promotion = flag == FLAG_PROMOTION || flag == FLAG_PROCAP;
+            let figure = if promotion {
+                PAWN_ID
+            } else {
+                g.board[di as usize].abs()
+            };
+            result = String::from(FIG_STR[figure as usize]);
             result.push(col_str(col(si)));
             result.push(row_str(row(si)));
             result.push(if flag == FLAG_CAPTURE || flag == FLAG_PROCAP {
@@ -2343,9 +2852,13 @@ pub fn move_to_str(g: &Game, si: Position, di: Position, flag: i32) -> String {
             });
             result.push(col_str(col(di)));
             result.push(row_str(row(di)));
-            if flag == FLAG_EP || flag == FLAG_PROCAP {
+            if flag == FLAG_EP {
                 result.push_str(" e.p.");
             }
+            if promotion {
+                result.push('=');
+                result.push(b" PNBRQK"[g.board[di as usize].unsigned_abs() as usize] as char);
+            }
         }
         if in_check(
             g,
@@ -2462,14 +2975,17 @@ fn setup_endgame(g: &mut Game) -> bool {
     true
 }
 
-pub fn reply(g: &mut Game) -> Move {
+// the best move for the side to move; setting `stop` ends the search early with the best
+// move of the last completed iteration
+pub fn reply(g: &mut Game, stop: &AtomicBool) -> Move {
     //let back_move
     let mut move_result = Move {
         state: STATE_NO_VALID_MOVE,
         score: LOWEST_SCORE,
         ..Default::default()
     };
-    let color = ((g.move_counter as i64 + 1) % 2) * 2 - 1;
+    let color = side_to_move(g);
+    g.root_color = color;
     let mut result: Move = Default::default();
     //println!("{:?}", g.freedom);
     if cfg!(feature = "salewskiChessDebug") {
@@ -2493,16 +3009,36 @@ pub fn reply(g: &mut Game) -> Move {
     }
     println!("--");
     g.time_4 = Duration::MAX;
-    while depth < MAX_DEPTH {
+    g.nodes = 0;
+    // a fixed depth search has no time limits
+    let fixed_depth = g.max_depth > 0;
+    let depth_limit = if fixed_depth {
+        g.max_depth.min(MAX_DEPTH)
+    } else {
+        MAX_DEPTH
+    };
+    while depth < depth_limit {
         depth += 1;
-        result = alphabeta(g, color, depth as i64, g.pjm);
+        // the first iteration always completes, so there is a move to return
+        let iteration_stop = if depth == 1 { &NO_STOP } else { stop };
+        result = alphabeta(g, color, depth as i64, g.pjm, iteration_stop);
         //println!("State {}", result.state);
         if result.score != LOWEST_SCORE {
+            result.depth = depth as i32;
             move_result = result;
-            g.time_4 = Duration::from_secs_f32(g.secs_per_move * 5.0);
+            if !fixed_depth {
+                g.time_4 = Duration::from_secs_f32(g.secs_per_move * 5.0);
+            }
+            if let Some(tx) = &g.search_info {
+                let _ = tx.send(SearchInfo {
+                    src: result.src,
+                    dst: result.dst,
+                });
+            }
         } else {
             assert!(move_result.score != LOWEST_SCORE);
             println!("--- hard cut");
+            move_result.nodes = g.nodes;
             return move_result;
         }
         println!(
@@ -2515,11 +3051,15 @@ pub fn reply(g: &mut Game) -> Move {
         if result.score.abs() > SURE_CHECKMATE as i64 {
             break;
         }
-        if start_time.elapsed() > g.time_0 {
+        if !fixed_depth && start_time.elapsed() > g.time_0 {
+            break;
+        }
+        if stop.load(Ordering::Relaxed) {
             break;
         }
         //if result.only_one_move { break; }
     }
+    result.nodes = g.nodes;
     result
 }
 
@@ -2626,3 +3166,52 @@ when false:
 */
 // 2715 lines 327 as
 // #e2e4 2.12s
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn best_move(fen: &str, depth: usize, aggression: i16) -> (Position, Position) {
+        let mut g = from_fen(fen).unwrap();
+        g.max_depth = depth;
+        g.aggression = aggression;
+        let m = reply(&mut g, &NO_STOP);
+        (m.src as Position, m.dst as Position)
+    }
+
+    #[test]
+    fn aggression_changes_the_choice() {
+        // Two Knights Defense: the neutral engine develops with Nc3, the aggressive one plays Bd5
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+        assert_eq!(best_move(fen, 3, 0), (6, 21));
+        assert_eq!(best_move(fen, 3, 10), (29, 36));
+    }
+
+    #[test]
+    fn fen_round_trip() {
+        let mut g = new_position();
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2",
+            "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 12 40",
+            "8/5k2/8/8/8/8/2K5/8 w - - 100 32767",
+        ] {
+            set_fen(&mut g, fen).unwrap();
+            assert_eq!(to_fen(&g), fen);
+        }
+    }
+
+    #[test]
+    fn fen_rejects_bad_counters() {
+        let mut g = new_position();
+        for fen in [
+            "8/5k2/8/8/8/8/2K5/8 w - - 101 1",
+            "8/5k2/8/8/8/8/2K5/8 w - - 256 1",
+            "8/5k2/8/8/8/8/2K5/8 w - - 0 0",
+            "8/5k2/8/8/8/8/2K5/8 w - - 0 32769",
+            "8/5k2/8/8/8/8/2K5/8 w - - 0 65536",
+        ] {
+            assert!(set_fen(&mut g, fen).is_err(), "{fen}");
+        }
+    }
+}
@@ -3,43 +3,107 @@
 // (C) 2015 - 2032 Dr. Stefan Salweski
 
 use std::{
-    sync::{Arc, Mutex, mpsc},
+    hash::{BuildHasher, Hasher, RandomState},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc,
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 //use masonry::properties::types::AsUnit;
-use masonry::layout::AsUnit;
 //use masonry::properties::types::Length;
+use masonry::core::PointerButton;
+use masonry::dpi::LogicalSize;
 use masonry::layout::Length;
 #[cfg(not(feature = "useSystemFont"))]
 use masonry::parley::FontStack;
-use masonry::{dpi::LogicalSize};
 use masonry_winit::app::{EventLoop, EventLoopBuilder};
 use tokio::time;
 use winit::error::EventLoopError;
-use xilem::view::CrossAxisAlignment;
 #[cfg(not(feature = "useSystemFont"))]
 use xilem::Blob;
+use xilem::view::CrossAxisAlignment;
 use xilem::{
     Color, WidgetView, WindowOptions, Xilem,
-    core::fork,
+    core::{fork, lens, one_of::Either},
     view::{
-        FlexExt, FlexSpacer, GridExt, button, checkbox, flex_col, flex_row, grid, label, prose,
-        sized_box, slider, task, text_button,
+        Alignment, FlexExt, FlexSpacer, GridExt, MainAxisAlignment, ZStackExt, button,
+        button_any_pointer, checkbox, flex_col, flex_row, grid, image, label, resize_observer,
+        sized_box, slider, task, text_button, text_input, zstack,
     },
 };
 //use xilem_core::Edit;
 use masonry::parley::style::LineHeight::FontSizeRelative;
 use xilem::style::Style;
 
+use uci::Backend;
+
+mod arena;
+mod arrows;
+mod audio;
+mod clipboard;
 mod engine;
+mod notation;
+mod openings;
+mod pgn;
+mod pieces;
+mod script;
+mod settings;
+mod speech;
+mod uci;
 
 const TIMER_TICK_MS: u64 = 100;
 const TIMER_TICK_SECS: f64 = TIMER_TICK_MS as f64 / 1000.0;
+/// Slower tick used in low-CPU mode while nothing is going on.
+const IDLE_TICK_MS: u64 = 500;
+/// Current tick interval in milliseconds, read by the background task. A static, as
+/// the task's init function must not capture anything.
+static TICK_MS: AtomicU64 = AtomicU64::new(TIMER_TICK_MS);
 const BOARD_SIZE: usize = 8;
 const GAP: Length = Length::const_px(12.0);
 const TINY_GAP: Length = Length::const_px(4.0);
+const NO_GAP: Length = Length::const_px(0.0);
+/// Width of the coordinate strips outside the board (logical pixels).
+const COORD_MARGIN: f64 = 20.0;
+const COORD_COLOR: Color = Color::from_rgb8(90, 90, 90);
+/// Board coordinates in dark mode.
+const DARK_COORD_COLOR: Color = Color::from_rgb8(180, 180, 180);
+/// Window background in dark mode.
+const DARK_BACKGROUND: Color = Color::from_rgb8(30, 30, 34);
+const STATUS_BAR_HEIGHT: Length = Length::const_px(28.0);
+/// Column widths of the move list: move number, white move, black move.
+const MOVE_NUMBER_WIDTH: Length = Length::const_px(36.0);
+const MOVE_WIDTH: Length = Length::const_px(100.0);
+/// Duration of the last-move pulse animation (seconds).
+const PULSE_SECS: f64 = 1.5;
+/// Number of pulses shown while the highlight fades out.
+const PULSE_COUNT: f64 = 3.0;
+/// Delay before a finished game is restarted in demo mode (seconds).
+const AUTO_RESTART_SECS: f64 = 5.0;
+/// Edge length of the pieces in the piece set preview (logical pixels).
+const PREVIEW_SIZE: f64 = 28.0;
+/// Default size of the pieces, as a fraction of the square.
+const PIECE_SCALE: f64 = 0.9;
+/// Line height of the piece glyphs in em.
+const GLYPH_LINE_HEIGHT: f64 = 1.1;
+/// Loss in centipawns, compared to the best move, from which a human move counts as blunder.
+const BLUNDER_CP: i64 = 150;
+/// Minimum time a position stays on screen before the engine starts its next search;
+/// keeps engine-vs-engine games at very short times per move watchable.
+const MIN_MOVE_SECS: f64 = 0.3;
+/// Delay before a forced human move is played automatically (seconds).
+const FORCED_MOVE_SECS: f64 = 0.8;
+/// Time within which a second click on "Resign?" confirms the resignation (seconds).
+const RESIGN_CONFIRM_SECS: f64 = 3.0;
+/// Upper limit for open game tabs; every engine game owns a large transposition table.
+const MAX_SESSIONS: usize = 4;
+/// Inner window size (logical pixels) at the first start, before one is saved.
+const INITIAL_WINDOW_SIZE: [f64; 2] = [1200.0, 1000.0];
+/// Smallest inner window size (logical pixels); saved sizes are raised to it.
+const MIN_WINDOW_SIZE: [f64; 2] = [800.0, 800.0];
 
 #[derive(Clone, Copy, Debug)]
 enum Piece {
@@ -85,8 +149,261 @@ enum Phase {
     EngineThinking,
     /// Engine move has been produced; apply it.
     EnginePlaying,
+    /// Guessing game: the human predicts the engine's next move before it searches.
+    Guessing,
+}
+
+/// How pieces are drawn on the board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PieceSet {
+    /// Unicode chess glyphs from the (bundled or system) font.
+    Unicode,
+    /// Bundled piece images, identical on all platforms.
+    Images(pieces::ImageSet),
+}
+
+impl PieceSet {
+    /// The images to draw, or `None` for glyphs, also if the images failed to load.
+    fn images(self) -> Option<&'static pieces::PieceImages> {
+        match self {
+            PieceSet::Unicode => None,
+            PieceSet::Images(set) => pieces::piece_images(set),
+        }
+    }
+}
+
+/// Where the board coordinates (files a-h, ranks 1-8) are drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CoordStyle {
+    None,
+    /// In strips left of and below the board.
+    Outside,
+    /// Small marks in the corners of the edge squares.
+    Inside,
+    Both,
+}
+
+/// Board and piece colors as RGB.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Theme {
+    light: (u8, u8, u8),
+    dark: (u8, u8, u8),
+    /// Color of the Unicode piece glyphs; image pieces keep their own colors.
+    piece: (u8, u8, u8),
+}
+
+const DEFAULT_THEME: Theme = Theme {
+    light: (255, 255, 255),
+    dark: (205, 205, 205),
+    piece: (0, 0, 0),
+};
+
+/// Muted board colors with light glyphs, chosen by dark mode.
+const DARK_THEME: Theme = Theme {
+    light: (120, 120, 130),
+    dark: (78, 78, 90),
+    piece: (235, 235, 235),
+};
+
+/// Named themes selectable with the preset button; the first one is the default.
+const THEME_PRESETS: [(&str, Theme); 5] = [
+    ("Gray", DEFAULT_THEME),
+    (
+        "Classic brown",
+        Theme {
+            light: (240, 217, 181),
+            dark: (181, 136, 99),
+            piece: (0, 0, 0),
+        },
+    ),
+    (
+        "Green/cream",
+        Theme {
+            light: (238, 238, 210),
+            dark: (118, 150, 86),
+            piece: (0, 0, 0),
+        },
+    ),
+    (
+        "Blue/gray",
+        Theme {
+            light: (222, 227, 230),
+            dark: (140, 162, 173),
+            piece: (0, 0, 0),
+        },
+    ),
+    ("Dark", DARK_THEME),
+];
+
+/// Background of a move clicked in the move list, and of its squares on the board.
+const MARKED_MOVE_COLOR: Color = Color::from_rgb8(170, 200, 255);
+
+/// Square of a king in check.
+const CHECK_COLOR: Color = Color::from_rgb8(240, 120, 120);
+
+/// Squares of the move suggested by "Hint".
+const HINT_COLOR: Color = Color::from_rgb8(150, 220, 150);
+
+/// Background of the result banner shown over the board when the game is over.
+const RESULT_BANNER_COLOR: Color = Color::from_rgba8(255, 255, 255, 220);
+
+/// Base times per side of the selectable time controls (seconds); 0 means untimed.
+const BASE_TIMES: [f64; 7] = [0.0, 60.0, 180.0, 300.0, 600.0, 900.0, 1800.0];
+
+/// Expected thinking time per move of a human player, for the clock pressure colors.
+const HUMAN_PACE_SECS: f64 = 30.0;
+
+/// Width of the material bar beside the board, and its distance from the board.
+const MATERIAL_BAR_WIDTH: f64 = 8.0;
+const MATERIAL_BAR_GAP: f64 = 4.0;
+
+/// Width of the evaluation bar on the other side of the board, and width of its column
+/// and height of the score label below it.
+const EVAL_BAR_WIDTH: f64 = 12.0;
+const EVAL_COLUMN_WIDTH: f64 = 36.0;
+const EVAL_LABEL_HEIGHT: f64 = 16.0;
+/// Centipawns at which the evaluation bar is split ten to one.
+const EVAL_SCALE_CP: f64 = 400.0;
+
+/// Below this contrast ratio between pieces and squares, the theme editor shows a warning.
+/// 3:1 is the WCAG minimum for graphical objects.
+const MIN_CONTRAST: f64 = 3.0;
+
+/// Material values of pawn, knight, bishop, rook and queen.
+const DEFAULT_PIECE_VALUES: [f64; 5] = [1.0, 3.0, 3.0, 5.0, 9.0];
+
+/// The theme color edited by the color sliders.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ThemePart {
+    Light,
+    Dark,
+    Piece,
+}
+
+/// A played move and the game time at which it was made, for replays.
+#[derive(Clone, Copy, Debug)]
+struct RecordedMove {
+    from: usize,
+    to: usize,
+    /// Engine figure ID used if the move promotes.
+    promote_to: i8,
+    /// Seconds since the start of the game (sum of both clocks).
+    at: f64,
+}
+
+/// A replay in progress; each move is played once the replay time reaches its timestamp,
+/// or, at a fixed pace, every `AppState::replay_step` seconds.
+struct Replay {
+    moves: Vec<RecordedMove>,
+    next: usize,
+    /// Seconds since the replay start, or since the last move at a fixed pace.
+    time: f64,
+    fixed_pace: bool,
+    paused: bool,
+    /// Ply of the blunder the review stopped at, see `AppState::next_blunder`.
+    blunder_at: Option<usize>,
+    /// Loss in centipawns of each move, as far as the blunder review has checked them.
+    losses: Vec<i64>,
+    /// Losses of the following moves, computed by a worker thread.
+    losses_rx: Option<mpsc::Receiver<i64>>,
+    /// Set while the review waits for the loss of a move it has to pass.
+    seeking: bool,
+}
+
+/// Position editor: the pieces as arranged by hand and the FEN fields set beside them.
+struct Editor {
+    board: BoardView,
+    /// Side to move, 0 for White.
+    turn: usize,
+    /// Castling rights in FEN order: White short, White long, Black short, Black long.
+    castling: [bool; 4],
+    /// Phase of the game to return to when editing is cancelled.
+    resume: Phase,
+}
+
+impl Editor {
+    /// The edited position as FEN, starting at move 1.
+    fn fen(&self) -> String {
+        let mut placement = String::new();
+        for rank in (0..BOARD_SIZE).rev() {
+            let mut empty = 0;
+            // The engine counts files from h to a.
+            for col in (0..BOARD_SIZE).rev() {
+                match self.board[rank][col] {
+                    Some(p) => {
+                        if empty > 0 {
+                            placement.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        let letter = b"PNBRQK"[p.piece as usize] as char;
+                        placement.push(match p.side {
+                            Side::White => letter,
+                            Side::Black => letter.to_ascii_lowercase(),
+                        });
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                placement.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                placement.push('/');
+            }
+        }
+        let castling: String = "KQkq"
+            .chars()
+            .zip(self.castling)
+            .filter_map(|(c, allowed)| allowed.then_some(c))
+            .collect();
+        format!(
+            "{placement} {} {} - 0 1",
+            if self.turn == 0 { 'w' } else { 'b' },
+            if castling.is_empty() { "-" } else { &castling }
+        )
+    }
+}
+
+/// Contents of a square after a click in the position editor: empty, then the white
+/// pieces from pawn to king, then the black ones.
+fn next_in_editor(square: Option<ColoredPiece>) -> Option<ColoredPiece> {
+    use Piece::*;
+    let next = |piece| match piece {
+        Pawn => Some(Knight),
+        Knight => Some(Bishop),
+        Bishop => Some(Rook),
+        Rook => Some(Queen),
+        Queen => Some(King),
+        King => None,
+    };
+    match square {
+        None => Some(ColoredPiece {
+            piece: Pawn,
+            side: Side::White,
+        }),
+        Some(p) => match (next(p.piece), p.side) {
+            (Some(piece), side) => Some(ColoredPiece { piece, side }),
+            (None, Side::White) => Some(ColoredPiece {
+                piece: Pawn,
+                side: Side::Black,
+            }),
+            (None, Side::Black) => None,
+        },
+    }
 }
 
+/// Named opening positions, given as coordinate moves from the initial position.
+const OPENINGS: [(&str, &str); 8] = [
+    ("Italian Game", "e2e4 e7e5 g1f3 b8c6 f1c4"),
+    ("Ruy Lopez", "e2e4 e7e5 g1f3 b8c6 f1b5"),
+    ("Sicilian Defence", "e2e4 c7c5"),
+    ("French Defence", "e2e4 e7e6 d2d4 d7d5"),
+    ("Caro-Kann Defence", "e2e4 c7c6 d2d4 d7d5"),
+    ("Queen's Gambit", "d2d4 d7d5 c2c4"),
+    ("King's Indian Defence", "d2d4 g8f6 c2c4 g7g6 b1c3 f8g7"),
+    ("English Opening", "c2c4"),
+];
+
 /// Map a "engine plays this side" boolean to a PlayerKind.
 const PLAYER_FOR_ENGINE_FLAG: [PlayerKind; 2] = [PlayerKind::Human, PlayerKind::Engine];
 
@@ -119,7 +436,212 @@ fn engine_to_board(engine_board: engine::Board) -> BoardView {
     board
 }
 
-fn piece_unicode(piece: ColoredPiece, solid: bool) -> &'static str {
+/// WCAG contrast ratio of two colors, from 1 (identical luminance) to 21 (black on white).
+fn contrast_ratio(a: Color, b: Color) -> f64 {
+    let luminance = |c: Color| {
+        let [r, g, b, _] = c.components.map(|v| {
+            let v = v as f64;
+            if v <= 0.04045 {
+                v / 12.92
+            } else {
+                ((v + 0.055) / 1.055).powf(2.4)
+            }
+        });
+        0.2126 * r + 0.7152 * g + 0.0722 * b
+    };
+    let (la, lb) = (luminance(a), luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+fn rgb_color((r, g, b): (u8, u8, u8)) -> Color {
+    Color::from_rgb8(r, g, b)
+}
+
+/// A warning if the piece glyphs are hard to see on light or dark squares.
+fn contrast_warning(theme: &Theme) -> Option<String> {
+    let piece = rgb_color(theme.piece);
+    let worst = contrast_ratio(piece, rgb_color(theme.light))
+        .min(contrast_ratio(piece, rgb_color(theme.dark)));
+    (worst < MIN_CONTRAST)
+        .then(|| format!("Low contrast ({worst:.1}:1), pieces may be hard to see"))
+}
+
+/// Material of [White, Black]; kings are not counted.
+fn material(board: &BoardView, values: &[f64; 5]) -> [f64; 2] {
+    let mut total = [0.0; 2];
+    for p in board.iter().flatten().flatten() {
+        if !matches!(p.piece, Piece::King) {
+            total[p.side as usize] += values[p.piece as usize];
+        }
+    }
+    total
+}
+
+/// Material of White minus material of Black.
+fn material_balance(board: &BoardView, values: &[f64; 5]) -> f64 {
+    let [white, black] = material(board, values);
+    white - black
+}
+
+/// Pieces of [White, Black] missing from the initial set, counted per piece type pawn ..
+/// queen. Promoted pieces make up for missing ones of their type.
+fn captured(board: &BoardView) -> [[u8; 5]; 2] {
+    const INITIAL: [u8; 5] = [8, 2, 2, 2, 1];
+    let mut count = [[0u8; 5]; 2];
+    for p in board.iter().flatten().flatten() {
+        if !matches!(p.piece, Piece::King) {
+            count[p.side as usize][p.piece as usize] += 1;
+        }
+    }
+    count.map(|side| std::array::from_fn(|i| INITIAL[i].saturating_sub(side[i])))
+}
+
+/// Rank shown in screen row `draw_row` (0 = top).
+fn rank_at(draw_row: usize, rotated: bool) -> char {
+    let row = if rotated {
+        draw_row
+    } else {
+        BOARD_SIZE - 1 - draw_row
+    };
+    (b'1' + row as u8) as char
+}
+
+/// File shown in screen column `draw_col` (0 = left).
+fn file_at(draw_col: usize, rotated: bool) -> char {
+    let col = if rotated {
+        draw_col
+    } else {
+        BOARD_SIZE - 1 - draw_col
+    };
+    (b'h' - col as u8) as char
+}
+
+/// Name of a linear engine square index, e.g. "e4".
+fn square_name(idx: usize) -> String {
+    let file = (b'h' - (idx % BOARD_SIZE) as u8) as char;
+    let rank = (b'1' + (idx / BOARD_SIZE) as u8) as char;
+    format!("{file}{rank}")
+}
+
+/// A game at the start position, or at `start_fen` if given, after playing `moves`. Its
+/// transposition table is small, which keeps it cheap to build; enough for looking at the
+/// position and for short searches.
+fn replayed_game(start_fen: Option<&str>, moves: &[RecordedMove]) -> engine::Game {
+    let mut game = engine::new_position();
+    replay_into(&mut game, start_fen, moves);
+    game
+}
+
+/// Set `game` to the start position, or to `start_fen` if given, and play `moves`; the
+/// transposition table of `game` is kept.
+fn replay_into(game: &mut engine::Game, start_fen: Option<&str>, moves: &[RecordedMove]) {
+    engine::reset_game(game);
+    if let Some(fen) = start_fen {
+        let _ = engine::set_fen(game, fen);
+    }
+    for mv in moves {
+        engine::do_move_promoting(game, mv.from as i8, mv.to as i8, mv.promote_to, false);
+    }
+}
+
+/// Square tags highlighting the move just played, including the rook of a castling.
+fn last_move_tags(from: usize, to: usize, flag: i32) -> engine::Board {
+    let mut tags = [0; 64];
+    tags[from] = 2;
+    tags[to] = 2;
+    if flag == engine::FLAG_CASTLING {
+        let (rook_from, rook_to) = engine::castling_rook(from as i8, to as i8);
+        tags[rook_from as usize] = 2;
+        tags[rook_to as usize] = 2;
+    }
+    tags
+}
+
+/// Compare a guessed move with the engine's move: the index into `AppState::guess_stats`
+/// and a word for the result. Moving the same piece or reaching the same square is close.
+fn guess_result(guess: (usize, usize), actual: (usize, usize)) -> (usize, &'static str) {
+    if guess == actual {
+        (0, "match")
+    } else if guess.0 == actual.0 || guess.1 == actual.1 {
+        (1, "close")
+    } else {
+        (2, "off")
+    }
+}
+
+/// Engine figure ID of a piece; the IDs run from 1 (pawn) to 6 (king), in `Piece` order.
+fn engine_id(piece: Piece) -> i8 {
+    piece as i8 + 1
+}
+
+/// Linear engine index of a square like "e4"; the engine counts files from h to a.
+fn square_index(square: &str) -> Option<usize> {
+    match square.as_bytes() {
+        &[file @ b'a'..=b'h', rank @ b'1'..=b'8'] => {
+            Some((rank - b'1') as usize * BOARD_SIZE + (b'h' - file) as usize)
+        }
+        _ => None,
+    }
+}
+
+/// Squares of a coordinate move like "e2e4".
+fn coordinate_move(mv: &str) -> Option<(usize, usize)> {
+    mv.get(0..2)
+        .and_then(square_index)
+        .zip(mv.get(2..4).and_then(square_index))
+}
+
+/// The next move of an `OPENINGS` line the game has followed so far, with the line's name;
+/// if several lines fit, one is picked at random.
+fn book_move(history: &[RecordedMove]) -> Option<(&'static str, (usize, usize))> {
+    let played: Vec<_> = history.iter().map(|m| (m.from, m.to)).collect();
+    let candidates: Vec<_> = OPENINGS
+        .iter()
+        .filter_map(|&(name, line)| {
+            let line: Vec<_> = line
+                .split_whitespace()
+                .filter_map(coordinate_move)
+                .collect();
+            (line.len() > played.len() && line.starts_with(&played))
+                .then(|| (name, line[played.len()]))
+        })
+        .collect();
+    (!candidates.is_empty()).then(|| candidates[random_index(candidates.len())])
+}
+
+/// A random number below `len`, which must not be 0.
+fn random_index(len: usize) -> usize {
+    (RandomState::new().build_hasher().finish() % len as u64) as usize
+}
+
+/// Describe the likely course of the game from a White-relative score in centipawns
+/// and an approximate number of moves to mate (0 if no mate was found).
+fn outcome_hint(score: i64, mate_in: i64) -> String {
+    let (leader, margin) = if score >= 0 {
+        ("White", score)
+    } else {
+        ("Black", -score)
+    };
+    match margin {
+        _ if mate_in > 0 => format!("{leader} mating, ~{mate_in} moves"),
+        500.. => format!("{leader} clearly winning"),
+        200..500 => format!("{leader} better"),
+        50..200 => format!("{leader} slightly better"),
+        _ => "Balanced position".into(),
+    }
+}
+
+/// Tint a square color by control: bluish for white, reddish for black control.
+fn control_tint((r, g, b): (u8, u8, u8), net: i8) -> (u8, u8, u8) {
+    let t = net.unsigned_abs().min(3) * 20;
+    if net > 0 {
+        (r.saturating_sub(t), g.saturating_sub(t / 2), b)
+    } else {
+        (r, g.saturating_sub(t / 2), b.saturating_sub(t))
+    }
+}
+
+fn piece_unicode(piece: ColoredPiece, solid: bool) -> char {
     use Piece::*;
     use Side::{Black, White};
 
@@ -127,18 +649,18 @@ fn piece_unicode(piece: ColoredPiece, solid: bool) -> &'static str {
     let effective_side = if solid { Black } else { piece.side };
 
     match (piece.piece, effective_side) {
-        (King, White) => "♔",
-        (Queen, White) => "♕",
-        (Rook, White) => "♖",
-        (Bishop, White) => "♗",
-        (Knight, White) => "♘",
-        (Pawn, White) => "♙",
-        (King, Black) => "♚",
-        (Queen, Black) => "♛",
-        (Rook, Black) => "♜",
-        (Bishop, Black) => "♝",
-        (Knight, Black) => "♞",
-        (Pawn, Black) => "♟︎",
+        (King, White) => '♔',
+        (Queen, White) => '♕',
+        (Rook, White) => '♖',
+        (Bishop, White) => '♗',
+        (Knight, White) => '♘',
+        (Pawn, White) => '♙',
+        (King, Black) => '♚',
+        (Queen, Black) => '♛',
+        (Rook, Black) => '♜',
+        (Bishop, Black) => '♝',
+        (Knight, Black) => '♞',
+        (Pawn, Black) => '♟',
     }
 }
 
@@ -164,26 +686,256 @@ struct AppState {
     engine_plays_black: bool,
     /// If true, use "solid" Unicode pieces (always black glyphs).
     use_solid_unicode: bool,
+    /// If true, the glyphs carry the text presentation selector, see `piece_view`.
+    text_presentation: bool,
+    /// Glyphs or images for the pieces.
+    piece_set: PieceSet,
+    /// Index into `OPENINGS` shown in the openings selector.
+    opening: usize,
     /// If true, draw board from white's perspective; otherwise black's.
     rotated: bool,
     /// If false, the periodic task isn't scheduled.
     active: bool,
     /// Time per engine move (seconds).
     time_per_move: f64,
+    /// If true, the engine searches to `search_depth` plies instead of by time.
+    depth_mode: bool,
+    /// Search depth in depth mode, 1 to 10 plies.
+    search_depth: f64,
+    /// Engine aggression, 0 (neutral) to 10; favors captures and king attacks.
+    aggression: f64,
+    /// Opening variety: during the first `opening_plies` plies the engine plays a random
+    /// move losing at most `opening_margin` centipawns; 0 plies disables it.
+    opening_plies: f64,
+    opening_margin: f64,
+    /// If true, the engine follows the `OPENINGS` lines while the game matches one.
+    use_book: bool,
+    /// Name of the opening line the engine's current move comes from; None for searched moves.
+    book_line: Option<&'static str>,
+    /// Polyglot book read by "Load book", from the file `book_path`.
+    polyglot: Option<openings::Book>,
+    book_path: String,
+    /// If true, the engine plays moves from `polyglot` while the position is in the book.
+    use_polyglot: bool,
+    /// True if the engine's current move comes from `polyglot`.
+    polyglot_move: bool,
     /// Accumulated clock time in seconds for [white, black].
     time_elapsed: [f64; 2],
+    /// Base time per side for a countdown clock (seconds); 0 for untimed games.
+    /// Kept for the next game.
+    base_time: f64,
+    /// Fischer increment per move for [white, black] (seconds), added after each move
+    /// with a countdown clock.
+    increment: [f64; 2],
+    /// Increments credited so far in this game for [white, black].
+    bonus: [f64; 2],
+    /// If true, neither clock runs; the game itself goes on.
+    clock_paused: bool,
+    /// If true, the clock of the side to move is colored by its time use against the pace.
+    clock_pressure: bool,
     /// Current side to move (0 = white, 1 = black).
     turn: usize,
     /// Pending human move as linear indices (from, to), if any.
     pending_move: Option<(usize, usize)>,
     /// Move list in text form.
     movelist: Vec<String>,
+    /// If true, the destination square of the last move pulses briefly.
+    pulse_last_move: bool,
+    /// Square currently pulsing and the remaining animation time (seconds).
+    pulse: Option<(usize, f64)>,
+    /// Training mode: flip the board after every move, so the side to move is at the bottom.
+    flip_each_move: bool,
+    /// If true, the tick loop slows down while this game is idle.
+    low_cpu: bool,
+    /// Wall-clock time of the previous tick; clocks advance by real elapsed time.
+    last_tick: Option<Instant>,
+    /// Set when a clock ran for another side than the one the engine has to move, which
+    /// indicates a phase/turn bug.
+    clock_fault: bool,
+    /// Demo mode: start a new game automatically after a game has ended.
+    auto_restart: bool,
+    /// In demo mode, let the players swap sides for the next game.
+    auto_restart_swap: bool,
+    /// Remaining seconds until the automatic restart, while a finished game is shown.
+    restart_countdown: Option<f64>,
+    /// Human readable estimate of the game's trajectory, from the last engine score.
+    outcome: String,
+    /// If true, tint squares by which side controls them.
+    show_control: bool,
+    /// Blindfold training: the board is drawn without pieces.
+    blindfold: bool,
+    /// In blindfold mode, still highlight the last move.
+    blindfold_last_move: bool,
+    /// Net control per square (white minus black attackers); updated while `show_control` is set.
+    control: [i8; 64],
+    /// Speak the moves of [white, black] aloud.
+    speak_moves: [bool; 2],
+    /// If true, moves are played without sound effects.
+    mute: bool,
+    /// Play the human's move automatically when it is the only legal one.
+    auto_forced: bool,
+    /// The only legal move of the human and the remaining delay before it is played.
+    forced_move: Option<((usize, usize), f64)>,
+    /// Board-only view: the settings panel and the tab bar are hidden.
+    minimal_view: bool,
+    /// Piece a human pawn promotes to, for [white, black]; engines use their own choice.
+    promotion: [Piece; 2],
+    /// If true, a human pawn move to the last rank asks for the piece instead of using
+    /// `promotion`.
+    ask_promotion: bool,
+    /// Pawn move to the last rank waiting for the choice of the piece.
+    promotion_prompt: Option<(usize, usize)>,
+    /// Piece chosen for the pending promotion; kept through a blunder confirmation.
+    promotion_choice: Option<Piece>,
+    /// Analysis overlay: show the engine's current best move while it is thinking.
+    analysis_overlay: bool,
+    /// Set by "Stop" to end the running search with its best move so far.
+    search_stop: Arc<AtomicBool>,
+    /// When the running search started, for the nodes per second.
+    search_started: Option<Instant>,
+    /// If true, the engine's moves come from the external UCI engine at `engine_path`.
+    external_engine: bool,
+    engine_path: String,
+    /// The external engine, started on first use and kept running between moves.
+    uci: Arc<Mutex<Option<uci::Uci>>>,
+    /// Why the external engine failed during the running search, if it did.
+    backend_error_rx: Option<mpsc::Receiver<String>>,
+    /// Progress messages of the running search.
+    info_rx: Option<mpsc::Receiver<engine::SearchInfo>>,
+    /// Best move of the running search so far, as linear indices (from, to).
+    considered: Option<(usize, usize)>,
+    /// Last move played, as linear indices (from, to); drawn as an arrow.
+    last_move: Option<(usize, usize)>,
+    /// Move suggested by "Hint", as linear indices (from, to), until the next click.
+    hint: Option<(usize, usize)>,
+    /// Result of the running hint search: the move and its notation.
+    hint_rx: Option<mpsc::Receiver<(usize, usize, String)>>,
+    /// Set to stop the running hint search.
+    hint_stop: Arc<AtomicBool>,
+    /// Annotation arrows drawn by the user with the right mouse button, as (from, to).
+    user_arrows: Vec<(usize, usize)>,
+    /// Square right-clicked first for a new annotation arrow.
+    arrow_start: Option<usize>,
+    /// Edge length of the board in logical pixels; the largest square fitting its area.
+    board_side: f64,
+    /// Size of the pieces as a fraction of the square, from the piece size slider.
+    piece_scale: f64,
+    /// Moves of the current game with their timestamps.
+    history: Vec<RecordedMove>,
+    /// Replay with the original timing, if one is running.
+    replay: Option<Replay>,
+    /// Event, site, round and date for the PGN header.
+    game_info: pgn::GameInfo,
+    /// If true, the game details section is expanded.
+    show_game_info: bool,
+    /// PGN result of the current game: "1-0", "0-1", "1/2-1/2" or "*" while running.
+    result: &'static str,
+    /// How the game ended, for the result banner; kept when the status line changes.
+    end_reason: String,
+    /// The position editor, while it is open; the game waits meanwhile.
+    editor: Option<Editor>,
+    /// Ask for confirmation before a human move that loses material or position.
+    blunder_warnings: bool,
+    /// Move held back as probable blunder, waiting for "Play anyway" or "Cancel".
+    blunder_prompt: Option<(usize, usize)>,
+    /// Set by "Play anyway"; the next move attempt skips the blunder check.
+    blunder_confirmed: bool,
+    /// Result of the running blunder check of the pending move: its loss in centipawns.
+    blunder_rx: Option<mpsc::Receiver<i64>>,
+    /// How board coordinates are shown.
+    coords: CoordStyle,
+    /// Colors of squares and pieces.
+    theme: Theme,
+    /// If true, the color editor is expanded.
+    show_theme: bool,
+    /// Dark window background and board colors.
+    dark_mode: bool,
+    /// Colors to go back to when dark mode is turned off.
+    light_theme: Theme,
+    /// Color currently edited in the color editor.
+    theme_part: ThemePart,
+    /// Seconds per move when replaying at a fixed pace.
+    replay_step: f64,
+    /// Seconds since the last move was applied.
+    since_move: f64,
+    /// Guessing game: predict each engine move before the engine plays it.
+    guess_mode: bool,
+    /// The human's prediction for the running engine search.
+    guess: Option<(usize, usize)>,
+    /// Guess results so far: [match, close, off].
+    guess_stats: [u32; 3],
+    /// With a piece selected, clicking another piece of the same color selects that one.
+    reselect_own: bool,
+    /// Piece values for the material display, see `DEFAULT_PIECE_VALUES`.
+    piece_values: [f64; 5],
+    /// If true, the piece value editor is expanded.
+    show_piece_values: bool,
+    /// Engine vs engine: wait for "Step" before each engine move.
+    step_mode: bool,
+    /// "Step" was pressed; the next engine move may start.
+    step_pending: bool,
+    /// Engine vs engine: no engine move starts until autoplay is resumed.
+    autoplay_paused: bool,
+    /// Engine vs engine: seconds between a move and the start of the next search.
+    autoplay_delay: f64,
+    /// Number of games a batch plays, from the batch slider.
+    batch_games: f64,
+    /// Games of the running batch not finished yet, the current one included; 0 without
+    /// a batch.
+    games_remaining: u32,
+    /// Results of the batch so far: [White wins, Black wins, draws].
+    score: [u32; 3],
+    /// If true, a bar beside the board shows White's and Black's share of the material.
+    show_material_bar: bool,
+    /// If true, a bar on the other side of the board shows the evaluation.
+    show_eval_bar: bool,
+    /// Evaluation in centipawns from White's view: the engine's score after its move,
+    /// otherwise the static evaluation of the position.
+    eval: i64,
+    /// FEN of the position `eval` belongs to.
+    eval_fen: String,
+    /// Ply of the move clicked in the move list; its squares are marked on the board.
+    marked_move: Option<usize>,
+    /// Earlier position shown on the board instead of the live one: the number of moves
+    /// played to reach it, and its pieces.
+    viewed: Option<(usize, BoardView)>,
+    /// Human vs human: "New game" lets chance decide which seat plays White.
+    fair_start: bool,
+    /// FEN of the position, refreshed with `board`.
+    fen: String,
+    /// Fullmove number, side to move and halfmove clock, refreshed with `board`.
+    counters: (u16, usize, u8),
+    /// Square of the king of the side to move while it is in check, refreshed with `board`.
+    checked_king: Option<usize>,
+    /// Beginner assist: when a human is in check, mark the pieces that can escape.
+    check_assist: bool,
+    /// Moves of the human in check as (from, to), while `check_assist` is on.
+    check_escapes: Vec<(usize, usize)>,
+    /// Text of the FEN input field.
+    fen_input: String,
+    /// Text of the move input field, a move like "Nf3" or "e2e4".
+    move_input: String,
+    /// FEN of the position the game started from, if set up with "Set position".
+    start_fen: Option<String>,
+    /// Keys of the positions of the game, from the start position on, for repetitions.
+    positions: Vec<u64>,
+    /// If true, a threefold repetition does not end the game; the side to move may
+    /// claim the draw instead.
+    claim_draws: bool,
+    /// Remaining time to confirm a resignation after the first click on "Resign".
+    resign_pending: Option<f64>,
+    /// Moves taken back, the next one to redo last; cleared by any other move.
+    redo_stack: Vec<RecordedMove>,
+    /// File written by "Save PGN" and read by "Load PGN".
+    pgn_path: String,
 }
 
 impl Default for AppState {
     fn default() -> Self {
-        let game = engine::new_game();
+        let mut game = engine::new_game();
         let board = engine_to_board(engine::get_board(&game));
+        let fen = engine::to_fen(&game);
+        let positions = vec![engine::position_key(&mut game)];
 
         Self {
             game: Arc::new(Mutex::new(game)),
@@ -197,204 +949,1883 @@ impl Default for AppState {
             engine_plays_white: false,
             engine_plays_black: true,
             use_solid_unicode: false,
+            text_presentation: true,
+            piece_set: PieceSet::Unicode,
+            opening: 0,
             rotated: false,
             active: true,
             time_per_move: 1.5,
+            depth_mode: false,
+            search_depth: 4.0,
+            aggression: 0.0,
+            opening_plies: 0.0,
+            opening_margin: 30.0,
+            use_book: false,
+            book_line: None,
+            polyglot: None,
+            book_path: "book.bin".into(),
+            use_polyglot: false,
+            polyglot_move: false,
             time_elapsed: [0.0, 0.0],
+            base_time: 0.0,
+            increment: [0.0, 0.0],
+            bonus: [0.0, 0.0],
+            clock_paused: false,
+            clock_pressure: false,
             turn: 0,
             pending_move: None,
             movelist: Vec::new(),
+            pulse_last_move: true,
+            pulse: None,
+            flip_each_move: false,
+            low_cpu: false,
+            last_tick: None,
+            clock_fault: false,
+            auto_restart: false,
+            auto_restart_swap: false,
+            restart_countdown: None,
+            outcome: String::new(),
+            show_control: false,
+            blindfold: false,
+            blindfold_last_move: true,
+            control: [0; 64],
+            speak_moves: [false, false],
+            mute: false,
+            auto_forced: false,
+            forced_move: None,
+            minimal_view: false,
+            promotion: [Piece::Queen; 2],
+            ask_promotion: true,
+            promotion_prompt: None,
+            promotion_choice: None,
+            analysis_overlay: false,
+            search_stop: Arc::new(AtomicBool::new(false)),
+            search_started: None,
+            external_engine: false,
+            engine_path: "stockfish".into(),
+            uci: Arc::new(Mutex::new(None)),
+            backend_error_rx: None,
+            info_rx: None,
+            considered: None,
+            last_move: None,
+            hint: None,
+            hint_rx: None,
+            hint_stop: Arc::new(AtomicBool::new(false)),
+            user_arrows: Vec::new(),
+            arrow_start: None,
+            board_side: 640.0,
+            piece_scale: PIECE_SCALE,
+            history: Vec::new(),
+            replay: None,
+            game_info: pgn::GameInfo::default(),
+            show_game_info: false,
+            result: "*",
+            end_reason: String::new(),
+            editor: None,
+            blunder_warnings: false,
+            blunder_prompt: None,
+            blunder_confirmed: false,
+            blunder_rx: None,
+            coords: CoordStyle::None,
+            theme: DEFAULT_THEME,
+            show_theme: false,
+            dark_mode: false,
+            light_theme: DEFAULT_THEME,
+            theme_part: ThemePart::Piece,
+            replay_step: 1.0,
+            since_move: MIN_MOVE_SECS,
+            guess_mode: false,
+            guess: None,
+            guess_stats: [0; 3],
+            reselect_own: true,
+            piece_values: DEFAULT_PIECE_VALUES,
+            show_piece_values: false,
+            step_mode: false,
+            step_pending: false,
+            autoplay_paused: false,
+            autoplay_delay: 1.0,
+            batch_games: 10.0,
+            games_remaining: 0,
+            score: [0; 3],
+            show_material_bar: false,
+            show_eval_bar: false,
+            eval: 0,
+            eval_fen: String::new(),
+            marked_move: None,
+            viewed: None,
+            fair_start: false,
+            fen,
+            counters: (1, 0, 0),
+            checked_king: None,
+            check_assist: false,
+            check_escapes: Vec::new(),
+            fen_input: String::new(),
+            move_input: String::new(),
+            start_fen: None,
+            positions,
+            claim_draws: false,
+            resign_pending: None,
+            redo_stack: Vec::new(),
+            pgn_path: "game.pgn".into(),
         }
     }
 }
 
 impl AppState {
+    /// Remaining time of `side` with a countdown clock; None for untimed games.
+    fn remaining(&self, side: usize) -> Option<f64> {
+        (self.base_time > 0.0).then(|| self.base_time + self.bonus[side] - self.time_elapsed[side])
+    }
+
+    /// Green, amber or red for the clock of the side to move. With a countdown clock this
+    /// depends on the share of the base time used up; otherwise on how its time compares
+    /// to the expected pace: `time_per_move` for the engine, `HUMAN_PACE_SECS` for a
+    /// human, for each move made so far and the current one.
+    fn clock_color(&self, side: usize) -> Option<Color> {
+        if !self.clock_pressure || side != self.turn || !self.outcome.is_empty() {
+            return None;
+        }
+        let ratio = match self.remaining(side) {
+            // Scaled so that half of the base time used up is 1.0.
+            Some(left) => 2.0 * (1.0 - left / self.base_time),
+            None => {
+                let pace = match self.players[side] {
+                    PlayerKind::Engine => self.time_per_move,
+                    PlayerKind::Human => HUMAN_PACE_SECS,
+                };
+                let moves_made = (self.movelist.len() + 1 - side) / 2;
+                self.time_elapsed[side] / ((moves_made + 1) as f64 * pace)
+            }
+        };
+        Some(match ratio {
+            r if r <= 1.0 => Color::from_rgb8(0, 150, 0),
+            r if r <= 1.5 => Color::from_rgb8(220, 150, 0),
+            _ => Color::from_rgb8(200, 0, 0),
+        })
+    }
+
     fn formatted_clock(secs: f64) -> String {
-        // Simple "MM:SS" display
+        // Simple "MM:SS" display; minutes go beyond 59 for long games and increments.
         let total = secs.round() as u64;
         let minutes = total / 60;
         let seconds = total % 60;
         format!("{minutes:02}:{seconds:02}")
     }
 
-    fn movelist_text(&self) -> String {
-        self.movelist
-            .chunks(2)
-            .enumerate()
-            .map(|(idx, chunk)| match chunk {
-                [a, b] => format!("{:>3}. {:>7}  {}", idx + 1, a, b),
-                [a] => format!("{:>3}. {:>7}", idx + 1, a),
-                _ => unreachable!(),
-            })
-            .collect::<Vec<_>>()
-            .join("\n")
+    /// Extra highlight shade for the pulsing last-move square, fading to zero.
+    fn pulse_shade(&self, idx: usize) -> u8 {
+        match self.pulse {
+            Some((sq, remaining)) if sq == idx && self.pulse_last_move => {
+                let elapsed = PULSE_SECS - remaining;
+                let phase = elapsed / PULSE_SECS * PULSE_COUNT * std::f64::consts::TAU;
+                let wave = 0.5 - 0.5 * phase.cos();
+                (wave * remaining / PULSE_SECS * 80.0) as u8
+            }
+            _ => 0,
+        }
     }
 
-    /// Called periodically by the Xilem `task` to advance the game / UI state.
-    fn tick(&mut self) {
-        // Only advance clocks during active phases.
-        if matches!(
-            self.phase,
-            Phase::Ready | Phase::MoveAttempt | Phase::EngineThinking | Phase::EnginePlaying
-        ) {
-            self.time_elapsed[self.turn] += TIMER_TICK_SECS;
-        }
+    /// Let the engine search a move for the human to move on a copy of the game; the
+    /// move is shown once `tick` receives it.
+    fn request_hint(&mut self) {
+        let start_fen = self.start_fen.clone();
+        let history = self.history.clone();
+        let secs_per_move = self.time_per_move as f32;
+        let max_depth = self.depth_mode.then(|| self.search_depth.round() as usize);
+        self.hint_stop = Arc::new(AtomicBool::new(false));
+        let stop = Arc::clone(&self.hint_stop);
+        let (tx, rx) = mpsc::channel();
+        self.hint_rx = Some(rx);
+        self.status = "Looking for a hint…".into();
+        thread::spawn(move || {
+            // The search needs a full transposition table, so the game is built here and
+            // not on the UI thread.
+            let mut game = engine::new_game();
+            replay_into(&mut game, start_fen.as_deref(), &history);
+            game.secs_per_move = secs_per_move;
+            if let Some(depth) = max_depth {
+                game.max_depth = depth;
+            }
+            let mv = engine::reply(&mut game, &stop);
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+            let (from, to) = (mv.src as i8, mv.dst as i8);
+            let flag = engine::do_move_promoting(&mut game, from, to, mv.promotion(), false);
+            let text = engine::move_to_str(&game, from, to, flag);
+            let _ = tx.send((from as usize, to as usize, text.trim().to_string()));
+        });
+    }
 
-        // Periodically sync our board view from the engine state.
-        if let Ok(game) = self.game.try_lock() {
+    /// Stop a running hint search and drop its move.
+    fn cancel_hint(&mut self) {
+        self.hint_stop.store(true, Ordering::Relaxed);
+        self.hint_rx = None;
+    }
+
+    /// Stop a running engine search and drop its move; the search thread then releases
+    /// the game lock within moments.
+    fn abandon_search(&mut self) {
+        self.search_stop.store(true, Ordering::Relaxed);
+        self.rx = None;
+        self.info_rx = None;
+        self.considered = None;
+    }
+
+    /// Start a new game from the initial position.
+    fn new_game(&mut self) {
+        self.abandon_search();
+        self.cancel_hint();
+        if let Ok(mut game) = self.game.lock() {
+            engine::reset_game(&mut game);
             self.board = engine_to_board(engine::get_board(&game));
+            self.fen = engine::to_fen(&game);
+            self.positions = vec![engine::position_key(&mut game)];
+            self.square_tags = [0; 64];
+            self.last_move = None;
+            self.user_arrows.clear();
+            self.arrow_start = None;
+            self.selected = None;
+            self.pending_move = None;
+            self.phase = Phase::Uninitialized;
+            self.time_elapsed = [0.0, 0.0];
+            self.bonus = [0.0, 0.0];
+            self.clock_paused = false;
+            self.movelist.clear();
+            self.history.clear();
+            self.replay = None;
+            self.editor = None;
+            self.result = "*";
+            self.end_reason.clear();
+            self.blunder_prompt = None;
+            self.blunder_rx = None;
+            self.promotion_prompt = None;
+            self.promotion_choice = None;
+            self.since_move = MIN_MOVE_SECS;
+            self.guess = None;
+            self.marked_move = None;
+            self.viewed = None;
+            self.pulse = None;
+            self.restart_countdown = None;
+            self.outcome.clear();
+            self.check_escapes.clear();
+            self.redo_stack.clear();
+            self.resign_pending = None;
+            self.start_fen = None;
+            self.hint = None;
         }
+    }
 
-        match self.phase {
-            Phase::Uninitialized => {
-                if let Ok(game) = self.game.lock() {
-                    let turn = game.move_counter as usize % 2;
-                    self.turn = turn;
-                    let player = self.players[turn];
-                    self.phase = match player {
-                        PlayerKind::Human => Phase::Ready,
-                        PlayerKind::Engine => Phase::EngineThinking,
-                    };
-                }
+    /// Start a new game from the position in the FEN input field; a malformed FEN is
+    /// reported in the status line.
+    fn set_position(&mut self) {
+        match engine::from_fen(&self.fen_input) {
+            Ok(game) => {
+                self.new_game();
+                self.start_from(game);
+                self.status = "Position set".into();
             }
-            Phase::MoveAttempt => {
-                if let Some((from_idx, to_idx)) = self.pending_move.take() {
-                    let from = from_idx as i8;
-                    let to = to_idx as i8;
+            Err(err) => self.status = err.to_string(),
+        }
+    }
 
-                    let mut game = self.game.lock().unwrap();
+    /// Open the position editor with the current position.
+    fn start_editing(&mut self) {
+        if matches!(
+            self.phase,
+            Phase::MoveAttempt | Phase::EngineThinking | Phase::EnginePlaying
+        ) || self.replay.is_some()
+        {
+            self.status = "Wait for the engine's move".into();
+            return;
+        }
+        let castling_field = self.fen.split_whitespace().nth(2).unwrap_or("-");
+        self.editor = Some(Editor {
+            board: self.board,
+            turn: self.turn,
+            castling: ['K', 'Q', 'k', 'q'].map(|c| castling_field.contains(c)),
+            resume: self.phase,
+        });
+        self.selected = None;
+        self.pending_move = None;
+        self.square_tags = [0; 64];
+        self.viewed = None;
+        self.marked_move = None;
+        self.hint = None;
+        self.cancel_hint();
+        self.phase = Phase::Inactive;
+        self.status = "Click squares to change their pieces".into();
+    }
 
-                    let valid = engine::move_is_valid2(&mut game, from as i64, to as i64);
+    /// Start a new game from the edited position, if it is legal; otherwise the editor
+    /// stays open and the status line says what is wrong.
+    fn finish_editing(&mut self) {
+        let Some(editor) = &self.editor else {
+            return;
+        };
+        match engine::from_fen(&editor.fen()) {
+            Ok(game) => {
+                self.new_game();
+                self.start_from(game);
+                self.status = "Position set".into();
+            }
+            Err(err) => self.status = format!("Illegal position: {err}"),
+        }
+    }
 
-                    self.square_tags = [0; 64];
+    /// Close the position editor and continue the game as it was.
+    fn cancel_editing(&mut self) {
+        if let Some(editor) = self.editor.take() {
+            self.phase = editor.resume;
+            self.status.clear();
+        }
+    }
 
-                    if from_idx == to_idx || !valid {
-                        self.status = "Invalid move.".into();
-                    } else {
-                        let flag = engine::do_move(&mut game, from, to, false);
-                        let notation = engine::move_to_str(&game, from, to, flag);
-                        self.movelist.push(notation.clone());
-                        self.status = notation;
-                        self.square_tags[from_idx] = 2;
-                        self.square_tags[to_idx] = 2;
-                    }
-                }
-                self.phase = Phase::Uninitialized;
-            }
-            Phase::EngineThinking => {
-                // Switch to "playing" and start a background thread to compute a move.
-                self.phase = Phase::EnginePlaying;
+    /// Play the move typed in the move field, with the same checks as a move made on the
+    /// board. Disambiguation like "Nbd2" and promotions like "e8=Q" are understood.
+    fn enter_move(&mut self) {
+        if self.phase != Phase::Ready || self.replay.is_some() {
+            self.status = "Not your move".into();
+            return;
+        }
+        let text = self.move_input.trim().to_string();
+        let (parsed, board) = {
+            let mut game = self.game.lock().unwrap();
+            (
+                notation::parse_move(&mut game, &text),
+                engine::get_board(&game),
+            )
+        };
+        let Some((from, to, promote_to)) = parsed else {
+            self.status = format!("Illegal or ambiguous move: {text}");
+            return;
+        };
+        let (from, to) = (from as usize, to as usize);
+        if board[from].abs() == 1 && !(BOARD_SIZE..BOARD_SIZE * (BOARD_SIZE - 1)).contains(&to) {
+            self.promotion_choice = Some(match promote_to {
+                2 => Piece::Knight,
+                3 => Piece::Bishop,
+                4 => Piece::Rook,
+                _ => Piece::Queen,
+            });
+        }
+        self.move_input.clear();
+        self.selected = None;
+        self.pending_move = Some((from, to));
+        self.phase = Phase::MoveAttempt;
+    }
 
-                if let Ok(mut game) = self.game.try_lock() {
-                    game.secs_per_move = self.time_per_move as f32;
-                }
+    /// Replace the freshly reset game by `game`, which starts from a set up position.
+    fn start_from(&mut self, game: engine::Game) {
+        let mut current = self.game.lock().unwrap();
+        *current = game;
+        self.board = engine_to_board(engine::get_board(&current));
+        self.fen = engine::to_fen(&current);
+        self.positions = vec![engine::position_key(&mut current)];
+        self.turn = engine::turn(&current);
+        self.start_fen = Some(self.fen.clone());
+    }
 
-                let (tx, rx) = mpsc::channel();
-                self.rx = Some(rx);
-                let game_clone = Arc::clone(&self.game);
+    /// How often the current position has occurred in this game.
+    fn repetitions(&self) -> usize {
+        let current = self.positions.last();
+        self.positions
+            .iter()
+            .filter(|&key| Some(key) == current)
+            .count()
+    }
 
-                thread::spawn(move || {
-                    let chess_move = engine::reply(&mut game_clone.lock().unwrap());
-                    let _ = tx.send(chess_move);
-                });
-            }
-            Phase::EnginePlaying => {
-                if let Some(rx) = &self.rx {
-                    if let Ok(mv) = rx.try_recv() {
-                        let mut game = self.game.lock().unwrap();
+    /// Why the game is drawn by rule, if it is.
+    fn draw_reason(&self) -> Option<&'static str> {
+        // Called between searches, so the lock is free and the check is never skipped.
+        if engine::is_insufficient_material(&self.game.lock().unwrap()) {
+            Some("Draw — insufficient material")
+        } else if self.counters.2 >= 100 {
+            Some("Draw by the fifty-move rule")
+        } else if !self.claim_draws && self.repetitions() >= 3 {
+            Some("Draw by threefold repetition")
+        } else {
+            None
+        }
+    }
 
-                        self.square_tags = [0; 64];
-                        self.square_tags[mv.src as usize] = 2;
-                        self.square_tags[mv.dst as usize] = 2;
+    /// End the game by resignation. With a single human player, the human resigns;
+    /// otherwise the side to move does. A running search is abandoned.
+    fn resign(&mut self) {
+        let side = match self.players {
+            [PlayerKind::Human, PlayerKind::Engine] => 0,
+            [PlayerKind::Engine, PlayerKind::Human] => 1,
+            _ => self.turn,
+        };
+        self.status = if side == 0 {
+            "White resigns — Black wins".into()
+        } else {
+            "Black resigns — White wins".into()
+        };
+        self.result = if side == 0 { "0-1" } else { "1-0" };
+        self.end_reason = self.status.clone();
+        self.resign_pending = None;
+        self.abandon_search();
+        self.phase = Phase::Inactive;
+    }
 
-                        let flag = engine::do_move(&mut game, mv.src as i8, mv.dst as i8, false);
-                        let notation = engine::move_to_str(&game, mv.src as i8, mv.dst as i8, flag);
+    /// End the game because the side to move ran out of time.
+    fn lose_on_time(&mut self) {
+        self.status = if self.turn == 0 {
+            "White loses on time".into()
+        } else {
+            "Black loses on time".into()
+        };
+        self.result = if self.turn == 0 { "0-1" } else { "1-0" };
+        self.end_reason = self.status.clone();
+        self.abandon_search();
+        self.phase = Phase::Inactive;
+    }
 
-                        self.movelist.push(notation.clone());
-                        self.status = format!("{notation} (scr: {})", mv.score);
+    /// End the game as a draw, with `reason` as status.
+    fn declare_draw(&mut self, reason: &str) {
+        self.status = reason.into();
+        self.result = "1/2-1/2";
+        self.end_reason = reason.into();
+        self.phase = Phase::Inactive;
+    }
 
-                        self.rx = None;
-                        self.phase = match mv.state {
-                            engine::STATE_CHECKMATE => {
-                                self.status = "Checkmate, game terminated!".into();
-                                Phase::Inactive
-                            }
-                            _ if mv.score.abs() > engine::KING_VALUE_DIV_2 as i64 => {
-                                let turns = mv.checkmate_in / 2 + if mv.score > 0 { -1 } else { 1 };
-                                self.status.push_str(&format!(" Checkmate in {}", turns));
-                                Phase::Uninitialized
-                            }
-                            _ => Phase::Uninitialized,
-                        };
-                    }
-                }
+    /// End the game after the side to move (`self.turn`) was found without a legal move.
+    fn finish_game(&mut self, state: engine::State) {
+        let winner = if self.turn == 0 { "Black" } else { "White" };
+        self.status = match state {
+            engine::STATE_CHECKMATE => format!("Checkmate — {winner} wins"),
+            _ => "Stalemate — draw".into(),
+        };
+        self.result = match state {
+            engine::STATE_CHECKMATE if self.turn == 0 => "0-1",
+            engine::STATE_CHECKMATE => "1-0",
+            _ => "1/2-1/2",
+        };
+        self.end_reason = self.status.clone();
+        self.phase = Phase::Inactive;
+    }
+
+    /// The game as PGN: the seven tag roster, SetUp and FEN tags for a set up position,
+    /// and the moves in SAN, wrapped at 80 columns.
+    fn to_pgn(&self) -> String {
+        let names = self.players.map(|p| format!("{p:?}"));
+        let mut pgn = self.game_info.headers(&names[0], &names[1], self.result);
+        let mut game = match &self.start_fen {
+            Some(fen) => {
+                pgn.push_str(&format!("[SetUp \"1\"]\n[FEN \"{fen}\"]\n"));
+                engine::from_fen(fen).unwrap_or_else(|_| engine::new_game())
             }
-            // Ready / Inactive and any other phases: nothing special on tick.
-            _ => {}
+            None => engine::new_game(),
+        };
+        pgn.push('\n');
+        let mut tokens = Vec::new();
+        for (ply, mv) in self.history.iter().enumerate() {
+            let number = engine::fullmove_number(&game);
+            if engine::turn(&game) == 0 {
+                tokens.push(format!("{number}."));
+            } else if ply == 0 {
+                tokens.push(format!("{number}..."));
+            }
+            tokens.push(notation::play_move(
+                &mut game,
+                mv.from as i8,
+                mv.to as i8,
+                mv.promote_to,
+            ));
+        }
+        tokens.push(self.result.to_string());
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > 80 {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
         }
+        pgn.push_str(&line);
+        pgn.push('\n');
+        pgn
     }
-}
-
-fn board_grid(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
-    let mut cells = Vec::with_capacity(BOARD_SIZE * BOARD_SIZE);
 
-    for row in 0..BOARD_SIZE {
-        for col in 0..BOARD_SIZE {
-            let idx = row * BOARD_SIZE + col;
+    /// Take back the last move; after an engine reply to a human, take back the human's
+    /// move as well. A running search is abandoned.
+    fn takeback(&mut self) {
+        if self.replay.is_some() || self.history.is_empty() {
+            return;
+        }
+        self.abandon_search();
+        let last_side = 1 - engine::turn(&self.game.lock().unwrap());
+        let count = if self.history.len() >= 2
+            && self.players[last_side] == PlayerKind::Engine
+            && self.players[1 - last_side] == PlayerKind::Human
+        {
+            2
+        } else {
+            1
+        };
+        self.rewind(self.history.len() - count);
+        self.status = if count == 2 {
+            "Took back two moves".into()
+        } else {
+            "Took back one move".into()
+        };
+    }
 
-            let (draw_row, draw_col) = if state.rotated {
-                (row, col)
-            } else {
-                (BOARD_SIZE - 1 - row, BOARD_SIZE - 1 - col)
-            };
+    /// Return to the position after the first `keep` moves; the later moves go to the redo
+    /// stack.
+    fn rewind(&mut self, keep: usize) {
+        self.abandon_search();
+        let mut game = self.game.lock().unwrap();
+        let start_fen = self.start_fen.as_deref();
+        debug_assert_eq!(
+            engine::position_key(&mut replayed_game(start_fen, &self.history)),
+            engine::position_key(&mut game),
+            "playing the moves again must restore the position"
+        );
+        replay_into(&mut game, start_fen, &self.history[..keep]);
+        self.redo_stack.extend(self.history.drain(keep..).rev());
+        self.positions.truncate(keep + 1);
+        self.movelist.truncate(keep);
+        self.board = engine_to_board(engine::get_board(&game));
+        self.fen = engine::to_fen(&game);
+        drop(game);
+        self.square_tags = [0; 64];
+        self.last_move = self.history.last().map(|mv| (mv.from, mv.to));
+        self.hint = None;
+        self.cancel_hint();
+        self.selected = None;
+        self.pending_move = None;
+        self.blunder_prompt = None;
+        self.blunder_rx = None;
+        self.guess = None;
+        self.marked_move = None;
+        self.viewed = None;
+        self.pulse = None;
+        self.forced_move = None;
+        self.restart_countdown = None;
+        self.result = "*";
+        self.outcome.clear();
+        self.phase = Phase::Uninitialized;
+    }
 
-            let shade = match state.square_tags[idx] {
-                2 => 25,
-                1 => 50,
-                _ => 0,
-            };
+    /// Show the position after `plies` moves of the game instead of the live one, and mark
+    /// the move leading to it; `None` or the full length shows the live position again.
+    /// Runs on every click in the move list, so the moves are replayed on a game with the
+    /// small table.
+    fn view(&mut self, plies: Option<usize>) {
+        self.marked_move = plies.and_then(|n| n.checked_sub(1));
+        self.viewed = plies.filter(|&n| n < self.history.len()).map(|n| {
+            let game = replayed_game(self.start_fen.as_deref(), &self.history[..n]);
+            (n, engine_to_board(engine::get_board(&game)))
+        });
+    }
 
-            let color = if (row + col) % 2 == 0 {
-                Color::from_rgb8(255, 255, 255 - shade)
-            } else {
-                Color::from_rgb8(205, 205, 205 - shade)
-            };
+    /// Continue the game from the position shown in the move list, dropping the later moves.
+    fn play_from_viewed(&mut self) {
+        if let Some((plies, _)) = self.viewed {
+            let dropped = self.history.len() - plies;
+            self.rewind(plies);
+            self.status = format!("Continuing from here; {dropped} later moves can be redone");
+        }
+    }
 
-            let label_text = state.board[row][col]
-                .map(|p| piece_unicode(p, state.use_solid_unicode))
-                .unwrap_or(" ");
+    /// Play the move taken back last again.
+    fn redo(&mut self) {
+        let Some(mv) = self.redo_stack.pop() else {
+            return;
+        };
+        self.abandon_search();
+        let mut game = self.game.lock().unwrap();
+        let flag =
+            engine::do_move_promoting(&mut game, mv.from as i8, mv.to as i8, mv.promote_to, false);
+        self.movelist
+            .push(engine::move_to_str(&game, mv.from as i8, mv.to as i8, flag));
+        self.history.push(mv);
+        self.positions.push(engine::position_key(&mut game));
+        self.square_tags = last_move_tags(mv.from, mv.to, flag);
+        self.last_move = Some((mv.from, mv.to));
+        self.board = engine_to_board(engine::get_board(&game));
+        self.fen = engine::to_fen(&game);
+        drop(game);
+        self.selected = None;
+        self.pending_move = None;
+        self.blunder_rx = None;
+        self.phase = Phase::Uninitialized;
+        self.status = "Redo".into();
+    }
 
-            let base = label(label_text).text_size(96.0);
-            #[cfg(not(feature = "useSystemFont"))]
-            let base = base.font(FontStack::Source("Noto Sans Symbols 2".into()));
-            let label_piece = base
-                .line_height(FontSizeRelative(1.1)) // needed for latest Xilem
-                .color(Color::BLACK);
+    /// Write the game as PGN to `pgn_path`.
+    fn save_pgn(&mut self) {
+        self.status = match std::fs::write(&self.pgn_path, self.to_pgn()) {
+            Ok(()) => format!("Saved {}", self.pgn_path),
+            Err(err) => format!("Cannot save {}: {err}", self.pgn_path),
+        };
+    }
 
-            let cell = button(label_piece, move |s: &mut AppState| {
-                let clicked = (row, col);
+    /// Read the Polyglot book in `book_path` and let the engine play from it.
+    fn load_book(&mut self) {
+        match openings::Book::load(&self.book_path) {
+            Ok(book) => {
+                self.polyglot = Some(book);
+                self.use_polyglot = true;
+                self.status = format!("Loaded {}", self.book_path);
+            }
+            Err(err) => self.status = format!("Cannot read {err}"),
+        }
+    }
 
-                match s.selected {
-                    None => {
-                        // First click: select a piece and show its legal moves.
-                        if s.board[row][col].is_some() {
-                            s.selected = Some(clicked);
-                            s.pending_move = None;
-                            s.square_tags = [0; 64];
+    /// Read the game in `pgn_path` and replay it. The game details are taken from the
+    /// tags; a move that is not valid stops the import at the position before it.
+    fn load_pgn(&mut self) {
+        let text = match std::fs::read_to_string(&self.pgn_path) {
+            Ok(text) => text,
+            Err(err) => {
+                self.status = format!("Cannot read {}: {err}", self.pgn_path);
+                return;
+            }
+        };
+        self.new_game();
+        if let Some(fen) = pgn::tag_value(&text, "FEN") {
+            match engine::from_fen(&fen) {
+                Ok(game) => self.start_from(game),
+                Err(err) => {
+                    self.status = err.to_string();
+                    return;
+                }
+            }
+        }
+        let info = &mut self.game_info;
+        for (name, field) in [
+            ("Event", &mut info.event),
+            ("Site", &mut info.site),
+            ("Round", &mut info.round),
+            ("Date", &mut info.date),
+        ] {
+            if let Some(value) = pgn::tag_value(&text, name) {
+                *field = value;
+            }
+        }
+        let mut failed = None;
+        {
+            let mut game = self.game.lock().unwrap();
+            for san in pgn::movetext_moves(&text) {
+                let number = engine::fullmove_number(&game);
+                let dots = if engine::turn(&game) == 0 { "." } else { "..." };
+                let Some((from, to, promote_to, flag)) = notation::apply_san(&mut game, &san)
+                else {
+                    failed = Some(format!("Move {number}{dots} {san} is not valid"));
+                    break;
+                };
+                self.movelist
+                    .push(engine::move_to_str(&game, from, to, flag));
+                self.history.push(RecordedMove {
+                    from: from as usize,
+                    to: to as usize,
+                    promote_to,
+                    at: 0.0,
+                });
+                self.positions.push(engine::position_key(&mut game));
+            }
+            self.board = engine_to_board(engine::get_board(&game));
+            self.fen = engine::to_fen(&game);
+        }
+        match failed {
+            Some(msg) => self.status = msg,
+            None => {
+                self.start_replay(true);
+                self.status = format!("Replaying {}", self.pgn_path);
+            }
+        }
+    }
+
+    /// Let the engine or a human play `side`. A search already running for that side
+    /// is not interrupted; its move is discarded on arrival if the side became human.
+    fn set_player(&mut self, side: usize, engine: bool) {
+        self.players[side] = PLAYER_FOR_ENGINE_FLAG[engine as usize];
+        if self.phase != Phase::EnginePlaying {
+            self.phase = Phase::Uninitialized;
+        } else if side == self.turn && !engine {
+            // The side now moves by hand; its search is no longer wanted.
+            self.abandon_search();
+            self.status = "Engine move discarded".into();
+            self.phase = Phase::Uninitialized;
+        }
+        self.orient_board();
+    }
+
+    /// Let the players swap sides.
+    fn swap_sides(&mut self) {
+        std::mem::swap(&mut self.engine_plays_white, &mut self.engine_plays_black);
+        self.players.swap(0, 1);
+        self.orient_board();
+    }
+
+    /// Put a single human player's side at the bottom. With two or no human players,
+    /// or when flipping after each move, the orientation stays as it is; "Rotate"
+    /// overrides it until the players change again.
+    fn orient_board(&mut self) {
+        if self.flip_each_move {
+            return;
+        }
+        // `rotated == false` shows White at the bottom.
+        match self.players {
+            [PlayerKind::Human, PlayerKind::Engine] => self.rotated = false,
+            [PlayerKind::Engine, PlayerKind::Human] => self.rotated = true,
+            _ => {}
+        }
+    }
+
+    /// Demo mode: count down after the game has ended, then start the next game.
+    fn tick_auto_restart(&mut self, dt: f64) {
+        if !self.auto_restart {
+            self.restart_countdown = None;
+            return;
+        }
+        let remaining = self.restart_countdown.unwrap_or(AUTO_RESTART_SECS) - dt;
+        if remaining > 0.0 {
+            self.restart_countdown = Some(remaining);
+        } else {
+            if self.auto_restart_swap {
+                self.swap_sides();
+            }
+            self.new_game();
+        }
+    }
+
+    /// Engine vs engine: play `batch_games` games in a row and count the results.
+    fn start_batch(&mut self) {
+        self.score = [0; 3];
+        self.games_remaining = self.batch_games.round() as u32;
+        self.new_game();
+        self.status = format!("Batch: game 1 of {}", self.batch_games.round());
+    }
+
+    /// Count the result of a finished batch game, then start the next one or report the
+    /// final score.
+    fn next_batch_game(&mut self) {
+        let index = match self.result {
+            "1-0" => 0,
+            "0-1" => 1,
+            _ => 2,
+        };
+        self.score[index] += 1;
+        self.games_remaining -= 1;
+        let [white, black, draws] = self.score;
+        let played = white + black + draws;
+        if self.games_remaining == 0 {
+            self.status =
+                format!("Batch done: {played} games, White {white}, Black {black}, draws {draws}");
+            return;
+        }
+        self.new_game();
+        self.status = format!(
+            "Batch: game {} of {}; White {white}, Black {black}, draws {draws}",
+            played + 1,
+            played + self.games_remaining
+        );
+    }
+
+    /// Start a new game and play the given coordinate moves, e.g. "e2e4 e7e5".
+    fn load_opening(&mut self, name: &str, moves: &str) {
+        self.new_game();
+        let mut game = self.game.lock().unwrap();
+        for mv in moves.split_whitespace() {
+            let Some((from, to)) = coordinate_move(mv) else {
+                self.status = format!("{name}: bad move {mv}");
+                return;
+            };
+            if !engine::move_is_valid2(&mut game, from as i64, to as i64) {
+                self.status = format!("{name}: illegal move {mv}");
+                return;
+            }
+            let flag = engine::do_move(&mut game, from as i8, to as i8, false);
+            self.movelist
+                .push(engine::move_to_str(&game, from as i8, to as i8, flag));
+            self.square_tags = last_move_tags(from, to, flag);
+            self.last_move = Some((from, to));
+            self.history.push(RecordedMove {
+                from,
+                to,
+                promote_to: engine_id(Piece::Queen),
+                at: 0.0,
+            });
+            self.positions.push(engine::position_key(&mut game));
+        }
+        self.board = engine_to_board(engine::get_board(&game));
+        self.fen = engine::to_fen(&game);
+        self.status = name.into();
+    }
+
+    /// Replay the moves of the current game from the start, either at the pace they
+    /// were played or at a fixed pace of `replay_step` seconds per move.
+    fn start_replay(&mut self, fixed_pace: bool) {
+        let moves = std::mem::take(&mut self.history);
+        let start_fen = self.start_fen.take();
+        self.new_game();
+        if let Some(game) = start_fen.and_then(|fen| engine::from_fen(&fen).ok()) {
+            self.start_from(game);
+        }
+        self.phase = Phase::Inactive;
+        self.status = "Replay".into();
+        self.replay = Some(Replay {
+            moves,
+            next: 0,
+            time: 0.0,
+            fixed_pace,
+            paused: false,
+            blunder_at: None,
+            losses: Vec::new(),
+            losses_rx: None,
+            seeking: false,
+        });
+    }
+
+    /// End a replay early; play continues from the position reached.
+    fn stop_replay(&mut self) {
+        self.replay = None;
+        self.status = "Replay stopped".into();
+        self.phase = Phase::Uninitialized;
+    }
+
+    /// Play all replay moves that are due; ends the replay after the last move.
+    fn tick_replay(&mut self, dt: f64) {
+        let Some(replay) = &mut self.replay else {
+            return;
+        };
+        if replay.paused {
+            if replay.seeking {
+                self.seek_blunder();
+            }
+            return;
+        }
+        replay.time += dt;
+        while let Some(replay) = &mut self.replay
+            && let Some(mv) = replay.moves.get(replay.next)
+        {
+            if replay.fixed_pace {
+                if replay.time < self.replay_step {
+                    break;
+                }
+                replay.time = 0.0;
+            } else if mv.at > replay.time {
+                break;
+            }
+            self.replay_move();
+        }
+        if let Some(replay) = &self.replay
+            && replay.next == replay.moves.len()
+        {
+            self.replay = None;
+            self.status = "Replay finished".into();
+        }
+    }
+
+    /// Play the next move of the replay, if any.
+    fn replay_move(&mut self) {
+        let Some(replay) = &mut self.replay else {
+            return;
+        };
+        let Some(&mv) = replay.moves.get(replay.next) else {
+            return;
+        };
+        replay.next += 1;
+        let mut game = self.game.lock().unwrap();
+        let flag =
+            engine::do_move_promoting(&mut game, mv.from as i8, mv.to as i8, mv.promote_to, false);
+        self.movelist
+            .push(engine::move_to_str(&game, mv.from as i8, mv.to as i8, flag));
+        self.history.push(mv);
+        self.positions.push(engine::position_key(&mut game));
+        self.square_tags = last_move_tags(mv.from, mv.to, flag);
+        self.last_move = Some((mv.from, mv.to));
+        self.pulse = Some((mv.to, PULSE_SECS));
+        self.turn = engine::turn(&game);
+        self.board = engine_to_board(engine::get_board(&game));
+        self.fen = engine::to_fen(&game);
+    }
+
+    /// Review: pause the replay (starting one if needed) just before the next move that
+    /// loses at least `BLUNDER_CP` centipawns, and show that move and its loss. The losses
+    /// are searched in a worker thread; the replay advances as they arrive.
+    fn next_blunder(&mut self) {
+        if self.replay.is_none() {
+            if self.history.is_empty() {
+                return;
+            }
+            self.start_replay(true);
+        }
+        let start_fen = self.start_fen.clone();
+        let Some(replay) = &mut self.replay else {
+            return;
+        };
+        replay.paused = true;
+        if replay.losses_rx.is_none() {
+            let moves = replay.moves.clone();
+            let (tx, rx) = mpsc::channel();
+            replay.losses_rx = Some(rx);
+            thread::spawn(move || {
+                let mut game = replayed_game(start_fen.as_deref(), &[]);
+                for mv in moves {
+                    let (from, to) = (mv.from as i8, mv.to as i8);
+                    if tx.send(engine::move_loss(&mut game, from, to)).is_err() {
+                        return;
+                    }
+                    engine::do_move_promoting(&mut game, from, to, mv.promote_to, false);
+                }
+            });
+        }
+        // Step over the blunder found last time.
+        if replay.blunder_at.is_some_and(|ply| ply == replay.next) {
+            self.replay_move();
+        }
+        if let Some(replay) = &mut self.replay {
+            replay.seeking = true;
+        }
+        self.seek_blunder();
+    }
+
+    /// Advance the paused replay of `next_blunder` over moves known not to be blunders.
+    fn seek_blunder(&mut self) {
+        if let Some(replay) = &mut self.replay
+            && let Some(rx) = &replay.losses_rx
+        {
+            replay.losses.extend(rx.try_iter());
+        }
+        while let Some(replay) = &mut self.replay
+            && replay.seeking
+            && let Some(&mv) = replay.moves.get(replay.next)
+        {
+            let ply = replay.next;
+            let Some(&loss) = replay.losses.get(ply) else {
+                self.status = "Looking for blunders…".into();
+                return;
+            };
+            if loss >= BLUNDER_CP {
+                replay.blunder_at = Some(ply);
+                replay.seeking = false;
+                let game = self.game.lock().unwrap();
+                let dots = if engine::turn(&game) == 1 { "…" } else { "" };
+                self.status = format!(
+                    "{}.{dots} {}-{} loses {:.1} pawns",
+                    engine::fullmove_number(&game),
+                    square_name(mv.from),
+                    square_name(mv.to),
+                    loss as f64 / 100.0
+                );
+                self.square_tags = [0; 64];
+                self.square_tags[mv.from] = -1;
+                self.square_tags[mv.to] = 1;
+                return;
+            }
+            self.replay_move();
+        }
+        if let Some(replay) = &mut self.replay
+            && replay.seeking
+        {
+            replay.seeking = false;
+            self.status = "No further blunders".into();
+        }
+    }
+
+    /// True while an engine vs engine game in step mode waits for the "Step" button.
+    fn awaiting_step(&self) -> bool {
+        self.step_mode
+            && !self.step_pending
+            && self.phase == Phase::EngineThinking
+            && self.players == [PlayerKind::Engine; 2]
+    }
+
+    /// True while an engine vs engine game waits for "Resume autoplay".
+    fn autoplay_held(&self) -> bool {
+        self.autoplay_paused
+            && self.phase == Phase::EngineThinking
+            && self.players == [PlayerKind::Engine; 2]
+    }
+
+    /// Time a position stays on screen before the engine starts its search.
+    fn move_delay(&self) -> f64 {
+        if self.players == [PlayerKind::Engine; 2] {
+            self.autoplay_delay.max(MIN_MOVE_SECS)
+        } else {
+            MIN_MOVE_SECS
+        }
+    }
+
+    /// Right-click on square `idx`: the first click starts an annotation arrow, the second
+    /// one on another square ends it, or removes the same arrow if it is already drawn.
+    /// A second click on the starting square clears all annotation arrows.
+    fn mark_arrow(&mut self, idx: usize) {
+        match self.arrow_start.take() {
+            None => {
+                self.arrow_start = Some(idx);
+                self.status = format!("Arrow from {}", square_name(idx));
+            }
+            Some(start) if start == idx => {
+                self.user_arrows.clear();
+                self.status = "Arrows cleared".into();
+            }
+            Some(start) => {
+                let arrow = (start, idx);
+                if let Some(pos) = self.user_arrows.iter().position(|&a| a == arrow) {
+                    self.user_arrows.remove(pos);
+                } else {
+                    self.user_arrows.push(arrow);
+                }
+                self.status.clear();
+            }
+        }
+    }
+
+    /// Select the piece on `square` (row, col) and tag its legal target squares.
+    fn select(&mut self, square: (usize, usize)) {
+        let idx = square.0 * BOARD_SIZE + square.1;
+        self.selected = Some(square);
+        self.pending_move = None;
+        self.square_tags = [0; 64];
+
+        for m in engine::tag(&mut self.game.lock().unwrap(), idx as i64) {
+            self.square_tags[m.di as usize] = 1;
+        }
+        self.square_tags[idx] = -1;
+        if self.phase != Phase::Guessing {
+            self.phase = Phase::Ready;
+        }
+    }
+
+    /// Speak a move just played by the side `self.turn`, if enabled for that side.
+    fn announce(&self, game: &mut engine::Game, notation: &str) {
+        if self.speak_moves[self.turn] {
+            let mate = engine::game_state(game) == engine::STATE_CHECKMATE;
+            speech::say(speech::describe(notation, mate));
+        }
+    }
+
+    /// Sound effect for the move just played in `game`; `flag` is the result of
+    /// `do_move_promoting`, and `self.board` still shows the position before the move.
+    fn play_sound(&self, game: &engine::Game, flag: i32) {
+        if self.mute {
+            return;
+        }
+        let before = self.board.iter().flatten().flatten().count();
+        let after = engine::get_board(game).iter().filter(|&&f| f != 0).count();
+        let sound = if engine::king_in_check(game).is_some() {
+            audio::Sound::Check
+        } else if flag == engine::FLAG_CASTLING {
+            audio::Sound::Castling
+        } else if after < before {
+            audio::Sound::Capture
+        } else {
+            audio::Sound::Move
+        };
+        audio::play(sound);
+    }
+
+    /// The preferences kept between runs.
+    fn settings(&self) -> settings::Settings {
+        let rgb = |(r, g, b): (u8, u8, u8)| Some([r, g, b]);
+        settings::Settings {
+            time_per_move: Some(self.time_per_move),
+            solid_unicode: Some(self.use_solid_unicode),
+            rotated: Some(self.rotated),
+            engine_plays_white: Some(self.engine_plays_white),
+            engine_plays_black: Some(self.engine_plays_black),
+            light: rgb(self.theme.light),
+            dark: rgb(self.theme.dark),
+            piece: rgb(self.theme.piece),
+            dark_mode: Some(self.dark_mode),
+            piece_values: Some(self.piece_values),
+            window_size: None,
+        }
+    }
+
+    /// Take over saved preferences; entries missing in `saved` keep their current value.
+    fn apply_settings(&mut self, saved: &settings::Settings) {
+        let rgb = |c: Option<[u8; 3]>, current| c.map_or(current, |[r, g, b]| (r, g, b));
+        if let Some(secs) = saved.time_per_move {
+            self.time_per_move = secs.clamp(0.1, 5.0);
+        }
+        self.use_solid_unicode = saved.solid_unicode.unwrap_or(self.use_solid_unicode);
+        self.engine_plays_white = saved.engine_plays_white.unwrap_or(self.engine_plays_white);
+        self.engine_plays_black = saved.engine_plays_black.unwrap_or(self.engine_plays_black);
+        self.set_player(0, self.engine_plays_white);
+        self.set_player(1, self.engine_plays_black);
+        // After the players, which turn the board towards a single human.
+        self.rotated = saved.rotated.unwrap_or(self.rotated);
+        self.dark_mode = saved.dark_mode.unwrap_or(self.dark_mode);
+        self.theme = Theme {
+            light: rgb(saved.light, self.theme.light),
+            dark: rgb(saved.dark, self.theme.dark),
+            piece: rgb(saved.piece, self.theme.piece),
+        };
+        if let Some(values) = saved.piece_values {
+            self.piece_values = values.map(|v| v.clamp(0.25, 12.0));
+        }
+    }
+
+    /// Switch dark mode on or off; turning it off brings back the colors used before.
+    fn set_dark_mode(&mut self, on: bool) {
+        if on && !self.dark_mode {
+            self.light_theme = self.theme;
+            self.theme = DARK_THEME;
+        } else if !on && self.dark_mode {
+            self.theme = self.light_theme;
+        }
+        self.dark_mode = on;
+    }
+
+    /// True when nothing but a human decision or a finished game is pending,
+    /// so the tick loop may run at a slower rate.
+    fn is_idle(&self) -> bool {
+        self.low_cpu
+            && self.pulse.is_none()
+            && self.resign_pending.is_none()
+            && self.forced_move.is_none()
+            && self.replay.is_none()
+            && (matches!(self.phase, Phase::Ready | Phase::Inactive | Phase::Guessing)
+                || self.awaiting_step()
+                || self.autoplay_held())
+    }
+
+    /// Called periodically by the Xilem `task` to advance the game / UI state.
+    fn tick(&mut self) {
+        // Use wall-clock deltas, so the clocks stay correct when ticks are slowed down.
+        let now = Instant::now();
+        let dt = self
+            .last_tick
+            .map_or(TIMER_TICK_SECS, |t| now.duration_since(t).as_secs_f64());
+        self.last_tick = Some(now);
+        let clocks_before = self.time_elapsed;
+
+        // Only advance clocks during active phases.
+        if matches!(
+            self.phase,
+            Phase::Ready | Phase::MoveAttempt | Phase::EngineThinking | Phase::EnginePlaying
+        ) && !self.awaiting_step()
+            && !self.autoplay_held()
+            && !self.clock_paused
+        {
+            self.time_elapsed[self.turn] += dt;
+            if self.remaining(self.turn).is_some_and(|left| left <= 0.0) {
+                self.time_elapsed[self.turn] = self.base_time + self.bonus[self.turn];
+                self.lose_on_time();
+            }
+        }
+        self.check_clocks(clocks_before);
+
+        self.since_move += dt;
+
+        if let Some(remaining) = self.resign_pending {
+            self.resign_pending = (remaining > dt).then_some(remaining - dt);
+        }
+
+        if let Some(rx) = &self.hint_rx
+            && let Ok((from, to, text)) = rx.try_recv()
+        {
+            self.hint = Some((from, to));
+            self.hint_rx = None;
+            self.status = format!("Hint: {text}");
+        }
+
+        // Fade out the last-move pulse.
+        if let Some((sq, remaining)) = self.pulse {
+            self.pulse = (remaining > dt).then_some((sq, remaining - dt));
+        }
+
+        // Periodically sync our board view from the engine state.
+        if let Ok(game) = self.game.try_lock() {
+            self.board = engine_to_board(engine::get_board(&game));
+            self.fen = engine::to_fen(&game);
+            self.counters = (
+                engine::fullmove_number(&game),
+                engine::turn(&game),
+                engine::halfmove_clock(&game),
+            );
+            self.checked_king = engine::king_in_check(&game).map(|k| k as usize);
+            if self.eval_fen != self.fen {
+                self.eval = engine::evaluate(&game);
+                self.eval_fen = self.fen.clone();
+            }
+            if self.show_control {
+                self.control = engine::control_map(&game);
+            }
+        }
+
+        match self.phase {
+            // A replay drives the board by itself.
+            _ if self.replay.is_some() => self.tick_replay(dt),
+            // The game waits while the position editor is open.
+            _ if self.editor.is_some() => {}
+            Phase::Uninitialized if let Some(reason) = self.draw_reason() => {
+                self.declare_draw(reason);
+            }
+            Phase::Uninitialized => {
+                if let Ok(mut game) = self.game.lock() {
+                    let turn = engine::turn(&game);
+                    self.turn = turn;
+                    if self.flip_each_move {
+                        // `rotated == false` shows white at the bottom.
+                        self.rotated = turn == 1;
+                    }
+                    let player = self.players[turn];
+                    self.phase = match player {
+                        PlayerKind::Human => Phase::Ready,
+                        PlayerKind::Engine if self.guess_mode => Phase::Guessing,
+                        PlayerKind::Engine => Phase::EngineThinking,
+                    };
+                    self.forced_move = None;
+                    self.check_escapes.clear();
+                    if self.check_assist
+                        && player == PlayerKind::Human
+                        && engine::king_in_check(&game).is_some()
+                    {
+                        self.check_escapes = engine::legal_moves(&mut game)
+                            .into_iter()
+                            .map(|(from, to)| (from as usize, to as usize))
+                            .collect();
+                        self.status = "You're in check — these moves escape".into();
+                    }
+                    if self.auto_forced
+                        && player == PlayerKind::Human
+                        && let [(from, to)] = engine::legal_moves(&mut game)[..]
+                    {
+                        self.forced_move = Some(((from as usize, to as usize), FORCED_MOVE_SECS));
+                    }
+                }
+            }
+            Phase::Ready => {
+                if let Some((mv, remaining)) = self.forced_move {
+                    if !self.auto_forced {
+                        self.forced_move = None;
+                    } else if remaining > dt {
+                        self.forced_move = Some((mv, remaining - dt));
+                    } else {
+                        self.forced_move = None;
+                        self.selected = None;
+                        self.pending_move = Some(mv);
+                        self.phase = Phase::MoveAttempt;
+                    }
+                }
+            }
+            Phase::MoveAttempt if let Some(rx) = &self.blunder_rx => match rx.try_recv() {
+                Err(mpsc::TryRecvError::Empty) => {}
+                loss => {
+                    self.blunder_rx = None;
+                    if loss.is_ok_and(|loss| loss >= BLUNDER_CP) {
+                        self.blunder_prompt = self.pending_move.take();
+                        self.status = "This looks like a blunder — play anyway?".into();
+                        self.phase = Phase::Uninitialized;
+                    } else {
+                        // The next tick plays the move.
+                        self.blunder_confirmed = true;
+                    }
+                }
+            },
+            Phase::MoveAttempt => {
+                let confirmed = std::mem::take(&mut self.blunder_confirmed);
+                let choice = self.promotion_choice.take();
+                let mut finished = None;
+                self.blunder_prompt = None;
+                self.promotion_prompt = None;
+                if let Some((from_idx, to_idx)) = self.pending_move.take() {
+                    let from = from_idx as i8;
+                    let to = to_idx as i8;
+
+                    let mut game = self.game.lock().unwrap();
+
+                    let valid = engine::move_is_valid2(&mut game, from as i64, to as i64);
+
+                    self.square_tags = [0; 64];
+
+                    let promotes = engine::get_board(&game)[from_idx].abs() == 1
+                        && !(BOARD_SIZE..BOARD_SIZE * (BOARD_SIZE - 1)).contains(&to_idx);
+
+                    if from_idx == to_idx || !valid {
+                        self.status = "Invalid move.".into();
+                    } else if promotes && self.ask_promotion && choice.is_none() {
+                        self.promotion_prompt = Some((from_idx, to_idx));
+                        self.status = "Promote to:".into();
+                    } else if self.blunder_warnings && !confirmed {
+                        // The check searches a few plies; it runs on a copy, so the board
+                        // stays responsive.
+                        let start_fen = self.start_fen.clone();
+                        let history = self.history.clone();
+                        let (tx, rx) = mpsc::channel();
+                        thread::spawn(move || {
+                            let mut copy = replayed_game(start_fen.as_deref(), &history);
+                            let _ = tx.send(engine::move_loss(&mut copy, from, to));
+                        });
+                        self.blunder_rx = Some(rx);
+                        self.pending_move = Some((from_idx, to_idx));
+                        self.promotion_choice = choice;
+                        self.status = "Checking the move…".into();
+                    } else {
+                        let promote_to = engine_id(choice.unwrap_or(self.promotion[self.turn]));
+                        let flag =
+                            engine::do_move_promoting(&mut game, from, to, promote_to, false);
+                        let state = engine::game_state(&mut game);
+                        let mut notation = engine::move_to_str(&game, from, to, flag);
+                        if state == engine::STATE_CHECKMATE
+                            && let Some(mv) = notation.strip_suffix(" +")
+                        {
+                            notation = format!("{mv} #");
+                        }
+                        finished = (state != engine::STATE_PLAYING).then_some(state);
+                        self.announce(&mut game, &notation);
+                        self.play_sound(&game, flag);
+                        self.movelist.push(notation.clone());
+                        self.history.push(RecordedMove {
+                            from: from_idx,
+                            to: to_idx,
+                            promote_to,
+                            at: self.time_elapsed.iter().sum(),
+                        });
+                        self.positions.push(engine::position_key(&mut game));
+                        self.redo_stack.clear();
+                        self.bonus[self.turn] += self.increment[self.turn];
+                        self.status = notation;
+                        self.square_tags = last_move_tags(from_idx, to_idx, flag);
+                        self.last_move = Some((from_idx, to_idx));
+                        self.pulse = Some((to_idx, PULSE_SECS));
+                        self.board = engine_to_board(engine::get_board(&game));
+                        self.fen = engine::to_fen(&game);
+                        self.since_move = 0.0;
+                    }
+                }
+                match finished {
+                    // The opponent of the human, now to move, has no legal move.
+                    Some(state) => {
+                        self.turn = 1 - self.turn;
+                        self.finish_game(state);
+                    }
+                    // Wait for the blunder check.
+                    None if self.blunder_rx.is_some() => {}
+                    None => self.phase = Phase::Uninitialized,
+                }
+            }
+            // Let the last move be seen before the engine replies.
+            Phase::EngineThinking
+                if self.since_move < self.move_delay()
+                    || self.awaiting_step()
+                    || self.autoplay_held() => {}
+            Phase::EngineThinking => {
+                // Never search without a legal move; the game is already over then.
+                let (info_tx, info_rx) = mpsc::channel();
+                // The book lines start from the initial position.
+                let book = (self.use_book && self.start_fen.is_none())
+                    .then(|| book_move(&self.history))
+                    .flatten();
+                let (state, book) = self
+                    .game
+                    .try_lock()
+                    .map(|mut game| {
+                        // With a countdown clock, never think longer than a 20th of the
+                        // remaining time.
+                        let limit = self
+                            .remaining(self.turn)
+                            .map_or(f64::MAX, |left| left / 20.0);
+                        game.secs_per_move = self.time_per_move.min(limit) as f32;
+                        game.max_depth = if self.depth_mode {
+                            self.search_depth.round() as usize
+                        } else {
+                            0
+                        };
+                        game.aggression = self.aggression.round() as i16;
+                        game.search_info = self.analysis_overlay.then_some(info_tx);
+                        // A move of the Polyglot book goes before the built-in lines.
+                        let polyglot = self
+                            .polyglot
+                            .as_ref()
+                            .filter(|_| self.use_polyglot)
+                            .and_then(|book| book.pick(&engine::to_fen(&game)))
+                            .and_then(|text| notation::parse_move(&mut game, &text))
+                            .map(|(from, to, promote_to)| {
+                                (None, from as usize, to as usize, promote_to)
+                            });
+                        let book = polyglot.or_else(|| {
+                            book.filter(|&(_, (from, to))| {
+                                engine::move_is_valid2(&mut game, from as i64, to as i64)
+                            })
+                            .map(|(name, (from, to))| {
+                                (Some(name), from, to, engine_id(Piece::Queen))
+                            })
+                        });
+                        (engine::game_state(&mut game), book)
+                    })
+                    .unwrap_or((engine::STATE_PLAYING, None));
+                self.info_rx = self.analysis_overlay.then_some(info_rx);
+                if state != engine::STATE_PLAYING {
+                    self.finish_game(state);
+                } else if let Some((name, from, to, promote_to)) = book {
+                    // Book moves need no search; hand the move over as if the engine sent it.
+                    self.phase = Phase::EnginePlaying;
+                    self.step_pending = false;
+                    self.book_line = name;
+                    self.polyglot_move = name.is_none();
+                    let mut chess_move = engine::Move::default();
+                    chess_move.src = from as i64;
+                    chess_move.dst = to as i64;
+                    chess_move.promote_to = promote_to as i64;
+                    let (tx, rx) = mpsc::channel();
+                    let _ = tx.send(chess_move);
+                    self.rx = Some(rx);
+                } else {
+                    // Switch to "playing" and start a background thread to compute a move.
+                    self.phase = Phase::EnginePlaying;
+                    self.step_pending = false;
+                    self.book_line = None;
+                    self.polyglot_move = false;
+
+                    let (tx, rx) = mpsc::channel();
+                    self.rx = Some(rx);
+                    let game_clone = Arc::clone(&self.game);
+                    let external = self
+                        .external_engine
+                        .then(|| (Arc::clone(&self.uci), self.engine_path.clone()));
+                    let (error_tx, error_rx) = mpsc::channel();
+                    self.backend_error_rx = Some(error_rx);
+                    // A fresh flag per search, so a late "Stop" can't end the next one.
+                    self.search_stop = Arc::new(AtomicBool::new(false));
+                    let stop = Arc::clone(&self.search_stop);
+                    self.search_started = Some(Instant::now());
+                    let random_plies = self.opening_plies.round() as u16;
+                    let margin = self.opening_margin.round() as i64;
+
+                    thread::spawn(move || {
+                        let mut game = game_clone.lock().unwrap();
+                        let result = match &external {
+                            Some((slot, path)) => {
+                                let mut slot = slot.lock().unwrap();
+                                let result = uci::connect(&mut slot, path)
+                                    .and_then(|external| external.best_move(&mut game, &stop));
+                                if result.is_err() {
+                                    // Start it anew next time, in case it was only this search.
+                                    *slot = None;
+                                }
+                                result
+                            }
+                            None => uci::BuiltIn.best_move(&mut game, &stop),
+                        };
+                        // Without a working external engine, the built-in one moves.
+                        let mut chess_move = result.unwrap_or_else(|err| {
+                            let _ = error_tx.send(err);
+                            engine::reply(&mut game, &stop)
+                        });
+                        // The search still runs, so random opening moves take the usual time.
+                        if game.move_counter < random_plies
+                            && let Some((src, dst)) = engine::random_good_move(&mut game, margin)
+                        {
+                            chess_move.src = src as i64;
+                            chess_move.dst = dst as i64;
+                            chess_move.promote_to = 0;
+                        }
+                        let _ = tx.send(chess_move);
+                    });
+                }
+            }
+            Phase::EnginePlaying => {
+                if let Some(info_rx) = &self.info_rx
+                    && let Some(info) = info_rx.try_iter().last()
+                {
+                    self.considered = Some((info.src as usize, info.dst as usize));
+                }
+                if let Some((from, to)) = self.guess
+                    && self.players[self.turn] == PlayerKind::Engine
+                {
+                    self.status = format!(
+                        "Your guess {}-{}, engine thinking…",
+                        square_name(from),
+                        square_name(to)
+                    );
+                }
+                let received = self.rx.as_ref().and_then(|rx| rx.try_recv().ok());
+                match received {
+                    // The side was switched to a human during the search; drop its move.
+                    Some(_) if self.players[self.turn] != PlayerKind::Engine => {
+                        self.rx = None;
+                        self.info_rx = None;
+                        self.considered = None;
+                        self.status = "Engine move discarded".into();
+                        self.phase = Phase::Uninitialized;
+                    }
+                    Some(mv) => {
+                        let mut game = self.game.lock().unwrap();
+
+                        self.info_rx = None;
+                        self.considered = None;
+
+                        let flag = engine::do_move_promoting(
+                            &mut game,
+                            mv.src as i8,
+                            mv.dst as i8,
+                            mv.promotion(),
+                            false,
+                        );
+                        let state = engine::game_state(&mut game);
+                        let mut notation =
+                            engine::move_to_str(&game, mv.src as i8, mv.dst as i8, flag);
+                        if state == engine::STATE_CHECKMATE
+                            && let Some(text) = notation.strip_suffix(" +")
+                        {
+                            notation = format!("{text} #");
+                        }
+                        self.square_tags = last_move_tags(mv.src as usize, mv.dst as usize, flag);
+                        self.last_move = Some((mv.src as usize, mv.dst as usize));
+                        self.announce(&mut game, &notation);
+                        self.play_sound(&game, flag);
+
+                        self.movelist.push(notation.clone());
+                        self.history.push(RecordedMove {
+                            from: mv.src as usize,
+                            to: mv.dst as usize,
+                            promote_to: mv.promotion(),
+                            at: self.time_elapsed.iter().sum(),
+                        });
+                        self.positions.push(engine::position_key(&mut game));
+                        self.redo_stack.clear();
+                        self.status = match self.book_line {
+                            Some(name) => format!("{notation} (book: {name})"),
+                            None if self.polyglot_move => format!("{notation} (book)"),
+                            None => format!("{notation} (scr: {}, depth {})", mv.score, mv.depth),
+                        };
+                        if let Some(err) = self
+                            .backend_error_rx
+                            .take()
+                            .and_then(|rx| rx.try_recv().ok())
+                        {
+                            self.external_engine = false;
+                            self.status = format!(
+                                "External engine failed ({err}), built-in engine: {}",
+                                self.status
+                            );
+                        }
+                        if let Some(started) = self.search_started.take()
+                            && mv.nodes > 0
+                        {
+                            let secs = started.elapsed().as_secs_f64().max(0.001);
+                            self.status.push_str(&format!(
+                                " ({:.1} Mnodes, {:.1} Mnps)",
+                                mv.nodes as f64 / 1e6,
+                                mv.nodes as f64 / secs / 1e6
+                            ));
+                        }
+                        if let Some(guess) = self.guess.take() {
+                            // No `&mut self` method here: `game` still borrows `self.game`.
+                            let (index, verdict) =
+                                guess_result(guess, (mv.src as usize, mv.dst as usize));
+                            self.guess_stats[index] += 1;
+                            self.status.push_str(&format!(" — your guess: {verdict}"));
+                        }
+                        self.pulse = Some((mv.dst as usize, PULSE_SECS));
+                        // Show the move in this tick already; the next search may hold the lock.
+                        self.board = engine_to_board(engine::get_board(&game));
+                        self.fen = engine::to_fen(&game);
+                        self.since_move = 0.0;
+                        self.bonus[self.turn] += self.increment[self.turn];
+
+                        // `self.turn` is still the engine's side; scores are from its view.
+                        let white_score = if self.turn == 0 { mv.score } else { -mv.score };
+                        let mate_in = if mv.score.abs() > engine::KING_VALUE_DIV_2 as i64 {
+                            (mv.checkmate_in as i64 / 2).max(1)
+                        } else {
+                            0
+                        };
+                        self.outcome = outcome_hint(white_score, mate_in);
+                        self.eval = white_score;
+                        self.eval_fen = self.fen.clone();
+
+                        self.rx = None;
+                        drop(game);
+                        if state != engine::STATE_PLAYING {
+                            // The engine's opponent, now to move, has no legal move.
+                            self.turn = 1 - self.turn;
+                            self.finish_game(state);
+                        } else {
+                            if mv.score.abs() > engine::KING_VALUE_DIV_2 as i64 {
+                                let turns = mv.checkmate_in / 2 + if mv.score > 0 { -1 } else { 1 };
+                                self.status.push_str(&format!(" Checkmate in {}", turns));
+                            }
+                            self.phase = Phase::Uninitialized;
+                        }
+                    }
+                    None => {}
+                }
+            }
+            Phase::Inactive if self.games_remaining > 0 => self.next_batch_game(),
+            Phase::Inactive => self.tick_auto_restart(dt),
+            // Any other phases: nothing special on tick.
+            _ => {}
+        }
+    }
+
+    /// Sanity check: while the engine thinks, the clock that ran must be the one of the
+    /// side to move in the game.
+    fn check_clocks(&mut self, before: [f64; 2]) {
+        if !matches!(self.phase, Phase::EngineThinking | Phase::EnginePlaying) {
+            return;
+        }
+        let Some(side) = before
+            .iter()
+            .zip(&self.time_elapsed)
+            .position(|(old, new)| new > old)
+        else {
+            return;
+        };
+        // A running search holds the lock; the side to move is then the one of the last sync.
+        let to_move = self
+            .game
+            .try_lock()
+            .map_or(self.counters.1, |game| engine::turn(&game));
+        // shown in the status bar of development builds
+        self.clock_fault |= side != to_move;
+    }
+}
+
+/// A piece (or an empty square) in the selected piece set, as glyph or image.
+/// `text_size` is also the edge length of an image.
+fn piece_view(
+    state: &AppState,
+    piece: Option<ColoredPiece>,
+    text_size: f32,
+) -> impl WidgetView<AppState> + use<> {
+    let mut label_text = piece
+        .map_or(' ', |p| piece_unicode(p, state.use_solid_unicode))
+        .to_string();
+    // U+FE0E asks for the monochrome text presentation. Of the chess symbols only the black
+    // pawn has an emoji form, but all glyphs get the selector so they are shaped alike.
+    if state.text_presentation && piece.is_some() {
+        label_text.push('\u{FE0E}');
+    }
+
+    let base = label(label_text).text_size(text_size);
+    #[cfg(not(feature = "useSystemFont"))]
+    let base = base.font(FontStack::Source(font_family().into()));
+    let label_piece = base
+        .line_height(FontSizeRelative(GLYPH_LINE_HEIGHT as f32)) // needed for latest Xilem
+        .color(rgb_color(state.theme.piece));
+
+    // Fall back to the glyphs if the images could not be loaded.
+    match (piece, state.piece_set.images()) {
+        (Some(p), Some(images)) => Either::A(
+            sized_box(image(images.get(p)))
+                .width(Length::const_px(text_size as f64))
+                .height(Length::const_px(text_size as f64)),
+        ),
+        _ => Either::B(label_piece),
+    }
+}
+
+/// A few pieces of both colors in the current piece set, shown next to the set selector.
+fn piece_set_preview(state: &AppState) -> impl WidgetView<AppState> + use<> {
+    let pieces = [Piece::King, Piece::Queen, Piece::Knight, Piece::Pawn];
+    let previews = [Side::White, Side::Black]
+        .into_iter()
+        .flat_map(|side| pieces.map(|piece| ColoredPiece { piece, side }))
+        .map(|p| {
+            sized_box(piece_view(state, Some(p), PREVIEW_SIZE as f32))
+                .width(Length::const_px(PREVIEW_SIZE))
+                .height(Length::const_px(PREVIEW_SIZE))
+        })
+        .collect::<Vec<_>>();
+    flex_row(previews)
+        .gap(NO_GAP)
+        .background_color(Color::from_rgb8(230, 230, 230))
+}
+
+/// The squares with their pieces; `square` is the edge length of a square in logical pixels.
+fn board_grid(state: &mut AppState, square: f64) -> impl WidgetView<AppState> + use<> {
+    let mut cells = Vec::with_capacity(BOARD_SIZE * BOARD_SIZE);
+    // Glyphs are centered in their squares with a spacer above them, twice as high
+    // as the font draws them above the middle of the line. The text size is chosen so
+    // that the line and the spacer fit the piece size, or the tall glyphs get clipped.
+    let images = state.piece_set.images().is_some();
+    let piece_size = square * state.piece_scale;
+    let (text_size, glyph_shift) = if images {
+        (piece_size, 0.0)
+    } else {
+        let text_size = piece_size / (GLYPH_LINE_HEIGHT + 2.0 * glyph_raise_em());
+        (text_size, 2.0 * glyph_raise_em() * text_size)
+    };
+    // The position editor's arrangement, or an earlier position from the move list, is
+    // shown without the live last move.
+    let (board, last_move) = match (&state.editor, state.viewed) {
+        (Some(editor), _) => (editor.board, None),
+        (None, Some((_, board))) => (board, None),
+        (None, None) => (state.board, state.last_move),
+    };
+    // Blindfolded, the pieces and optionally the last move are left out; the position
+    // editor always shows its pieces.
+    let blind = state.blindfold && state.editor.is_none();
+    let hide_last_move = blind && !state.blindfold_last_move;
+    let last_move = last_move.filter(|_| !hide_last_move);
+    let arrows = arrows::arrows(last_move, &state.user_arrows, state.rotated);
+
+    for row in 0..BOARD_SIZE {
+        for col in 0..BOARD_SIZE {
+            let idx = row * BOARD_SIZE + col;
+
+            let (draw_row, draw_col) = if state.rotated {
+                (row, col)
+            } else {
+                (BOARD_SIZE - 1 - row, BOARD_SIZE - 1 - col)
+            };
+
+            // In check, the selected piece's escapes are drawn stronger; with nothing
+            // selected, the pieces that can escape are marked.
+            let escaping = !state.check_escapes.is_empty();
+            let shade = match state.square_tags[idx] {
+                2 if hide_last_move => 0,
+                2 => 25,
+                1 if escaping => 90,
+                1 => 50,
+                _ if escaping
+                    && state.selected.is_none()
+                    && state.check_escapes.iter().any(|&(from, _)| from == idx) =>
+                {
+                    50
+                }
+                _ => 0,
+            };
+            // The pulse only layers on top of the last-move tag, never on a selection.
+            let shade = if state.square_tags[idx] == 2 && !hide_last_move {
+                shade + state.pulse_shade(idx)
+            } else {
+                shade
+            };
+
+            let (r, g, b) = if (row + col) % 2 == 0 {
+                state.theme.light
+            } else {
+                state.theme.dark
+            };
+            let rgb = (r, g, b.saturating_sub(shade));
+            let rgb = if state.show_control {
+                control_tint(rgb, state.control[idx])
+            } else {
+                rgb
+            };
+            // The engine's tentative move is drawn greenish, unlike the confirmed last move.
+            let considered = state
+                .considered
+                .is_some_and(|(from, to)| idx == from || idx == to);
+            let (r, g, b) = if considered && state.analysis_overlay {
+                (rgb.0.saturating_sub(70), rgb.1, rgb.2.saturating_sub(70))
+            } else {
+                rgb
+            };
+            let marked = state
+                .marked_move
+                .and_then(|ply| state.history.get(ply))
+                .is_some_and(|m| idx == m.from || idx == m.to);
+            let color = if marked {
+                MARKED_MOVE_COLOR
+            } else if state.checked_king == Some(idx) {
+                CHECK_COLOR
+            } else if state
+                .hint
+                .is_some_and(|(from, to)| idx == from || idx == to)
+            {
+                HINT_COLOR
+            } else {
+                Color::from_rgb8(r, g, b)
+            };
 
-                            for m in engine::tag(&mut s.game.lock().unwrap(), idx as i64) {
-                                s.square_tags[m.di as usize] = 1;
-                            }
-                            s.square_tags[idx] = -1;
-                            s.phase = Phase::Ready;
+            // Coordinate marks inside the squares of the left column and the bottom row.
+            let inside = matches!(state.coords, CoordStyle::Inside | CoordStyle::Both);
+            let mark = |text: char, alignment: Alignment| {
+                let mark = label(text.to_string())
+                    .text_size(14.0)
+                    .color(coord_color(state.dark_mode));
+                ZStackExt::alignment(mark, alignment)
+            };
+            let content = zstack((
+                flex_col((
+                    FlexSpacer::Fixed(Length::const_px(glyph_shift)),
+                    piece_view(state, board[row][col].filter(|_| !blind), text_size as f32),
+                ))
+                .gap(NO_GAP),
+                // This square's part of the arrows, drawn over the piece.
+                arrows
+                    .as_ref()
+                    .and_then(|slices| slices[idx].as_ref())
+                    .map(image),
+                (inside && draw_col == 0)
+                    .then(|| mark(rank_at(draw_row, state.rotated), Alignment::TopLeading)),
+                (inside && draw_row == BOARD_SIZE - 1)
+                    .then(|| mark(file_at(draw_col, state.rotated), Alignment::BottomTrailing)),
+            ));
+
+            let cell = button_any_pointer(content, move |s: &mut AppState, pressed| {
+                if pressed == Some(PointerButton::Secondary) {
+                    s.mark_arrow(idx);
+                    return;
+                }
+                if let Some(editor) = &mut s.editor {
+                    editor.board[row][col] = next_in_editor(editor.board[row][col]);
+                    return;
+                }
+                s.hint = None;
+                s.cancel_hint();
+                if s.replay.is_some() {
+                    return;
+                }
+                if s.viewed.is_some() {
+                    s.status = "Showing an earlier position; press > to return".into();
+                    return;
+                }
+                // Only the human to move (or guessing) may select and move. In the other
+                // phases the engine may hold the game, or a move is on its way.
+                if !matches!(s.phase, Phase::Ready | Phase::Guessing) {
+                    return;
+                }
+                let clicked = (row, col);
+
+                match s.selected {
+                    None => {
+                        // First click: select a piece of the side to move and show its
+                        // legal moves.
+                        match s.board[row][col] {
+                            Some(p) if p.side as usize == s.turn => s.select(clicked),
+                            Some(_) => s.status = "Not your turn to move that".into(),
+                            None => {}
                         }
                     }
+                    Some(prev)
+                        if s.reselect_own
+                            && prev != clicked
+                            && let (Some(a), Some(b)) =
+                                (s.board[prev.0][prev.1], s.board[row][col])
+                            && a.side == b.side =>
+                    {
+                        // Clicking another piece of the same color moves the selection there.
+                        s.select(clicked);
+                    }
                     Some(prev) if prev != clicked => {
                         // Second click: attempt a move.
                         let from_idx = prev.0 * BOARD_SIZE + prev.1;
-                        s.pending_move = Some((from_idx, idx));
                         s.selected = None;
-                        s.phase = Phase::MoveAttempt;
+                        if s.phase == Phase::Guessing {
+                            // The guess is only recorded; the engine plays its own move.
+                            s.square_tags = [0; 64];
+                            let legal = engine::move_is_valid2(
+                                &mut s.game.lock().unwrap(),
+                                from_idx as i64,
+                                idx as i64,
+                            );
+                            if legal {
+                                s.guess = Some((from_idx, idx));
+                                s.phase = Phase::EngineThinking;
+                            } else {
+                                s.status = "Not a legal move, guess again.".into();
+                            }
+                        } else {
+                            s.pending_move = Some((from_idx, idx));
+                            s.phase = Phase::MoveAttempt;
+                        }
                     }
                     Some(_) => {
                         // Second click on same square: deselect.
@@ -409,113 +2840,1482 @@ fn board_grid(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
             .corner_radius(0.0)
             .grid_pos(draw_col as i32, draw_row as i32);
 
-            cells.push(cell);
+            cells.push(cell);
+        }
+    }
+
+    grid(cells, BOARD_SIZE as i32, BOARD_SIZE as i32)
+}
+
+fn clock_label(state: &AppState, side: usize) -> impl WidgetView<AppState> + use<> {
+    let name = if side == 0 { "White" } else { "Black" };
+    let secs = state
+        .remaining(side)
+        .map_or(state.time_elapsed[side], |left| left.max(0.0));
+    let text = label(format!("{name}: {}", AppState::formatted_clock(secs)));
+    match state.clock_color(side) {
+        Some(color) => Either::A(text.color(color)),
+        None => Either::B(text),
+    }
+}
+
+/// Fischer increment of `side`, 0 to 30 whole seconds.
+fn increment_slider(
+    state: &AppState,
+    side: usize,
+    name: &str,
+) -> impl WidgetView<AppState> + use<> {
+    flex_col((
+        label(format!("{name} increment: {:.0} s", state.increment[side])),
+        slider(
+            0.0,
+            30.0,
+            state.increment[side],
+            move |s: &mut AppState, val| {
+                s.increment[side] = val.round();
+            },
+        ),
+    ))
+    .cross_axis_alignment(CrossAxisAlignment::Start)
+}
+
+fn clock_section(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
+    flex_col((
+        clock_label(state, 0),
+        clock_label(state, 1),
+        text_button(
+            if state.clock_paused {
+                "Resume"
+            } else {
+                "Pause"
+            },
+            |s: &mut AppState| {
+                s.clock_paused = !s.clock_paused;
+            },
+        ),
+        text_button(
+            if state.base_time > 0.0 {
+                format!("Time control: {} min", state.base_time / 60.0)
+            } else {
+                "Time control: untimed".into()
+            },
+            |s: &mut AppState| {
+                let next = BASE_TIMES
+                    .iter()
+                    .position(|&t| t == s.base_time)
+                    .map_or(0, |i| i + 1);
+                s.base_time = BASE_TIMES[next % BASE_TIMES.len()];
+            },
+        ),
+        (state.base_time > 0.0).then(|| {
+            flex_col((
+                increment_slider(state, 0, "White"),
+                increment_slider(state, 1, "Black"),
+            ))
+            .cross_axis_alignment(CrossAxisAlignment::Start)
+            .gap(TINY_GAP)
+        }),
+        checkbox(
+            "Clock pressure colors",
+            state.clock_pressure,
+            |s: &mut AppState, _| {
+                s.clock_pressure = !s.clock_pressure;
+            },
+        ),
+        (!state.outcome.is_empty()).then(|| label(&*state.outcome)),
+        flex_row((
+            label(format!(
+                "Move {}, {} to move",
+                state.counters.0,
+                if state.counters.1 == 0 {
+                    "White"
+                } else {
+                    "Black"
+                }
+            )),
+            label(format!("Fifty-move count: {}/100", state.counters.2)),
+        ))
+        .gap(GAP),
+        label(match material_balance(&state.board, &state.piece_values) {
+            m if m.abs() < 0.01 => "Material: even".to_string(),
+            m if m > 0.0 => format!("Material: White +{m}"),
+            m => format!("Material: Black +{}", -m),
+        }),
+        state.guess_mode.then(|| {
+            let [hit, close, off] = state.guess_stats;
+            label(format!("Guesses: {hit} match, {close} close, {off} off"))
+        }),
+    ))
+    .cross_axis_alignment(CrossAxisAlignment::Start)
+    .gap(TINY_GAP)
+}
+
+fn engine_section(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
+    flex_col((
+        label(format!("{:.2} sec/move", state.time_per_move)),
+        slider(
+            0.1,
+            5.0,
+            state.time_per_move,
+            |state: &mut AppState, val| {
+                state.time_per_move = val;
+            },
+        ),
+        checkbox(
+            "Search to a fixed depth",
+            state.depth_mode,
+            |s: &mut AppState, _| {
+                s.depth_mode = !s.depth_mode;
+            },
+        ),
+        state.depth_mode.then(|| {
+            flex_col((
+                label(format!("Depth: {:.0} plies", state.search_depth)),
+                slider(1.0, 10.0, state.search_depth, |s: &mut AppState, val| {
+                    s.search_depth = val.round();
+                }),
+            ))
+            .cross_axis_alignment(CrossAxisAlignment::Start)
+            .gap(TINY_GAP)
+        }),
+        label(format!("Aggression: {:.0}", state.aggression)),
+        slider(0.0, 10.0, state.aggression, |state: &mut AppState, val| {
+            state.aggression = val;
+        }),
+        label(format!(
+            "Opening variety: {:.0} plies, {:.0} cp",
+            state.opening_plies, state.opening_margin
+        )),
+        slider(
+            0.0,
+            20.0,
+            state.opening_plies,
+            |state: &mut AppState, val| {
+                state.opening_plies = val;
+            },
+        ),
+        slider(
+            0.0,
+            100.0,
+            state.opening_margin,
+            |state: &mut AppState, val| {
+                state.opening_margin = val;
+            },
+        ),
+        checkbox(
+            "Engine uses opening book",
+            state.use_book,
+            |s: &mut AppState, _| {
+                s.use_book = !s.use_book;
+            },
+        ),
+        checkbox(
+            "Guess the engine's move",
+            state.guess_mode,
+            |s: &mut AppState, _| {
+                s.guess_mode = !s.guess_mode;
+                if s.phase == Phase::Guessing {
+                    s.phase = Phase::Uninitialized;
+                }
+            },
+        ),
+        flex_row((
+            checkbox(
+                "Engine plays white",
+                state.engine_plays_white,
+                |s: &mut AppState, _| {
+                    s.engine_plays_white = !s.engine_plays_white;
+                    s.set_player(0, s.engine_plays_white);
+                },
+            ),
+            checkbox(
+                "Engine plays black",
+                state.engine_plays_black,
+                |s: &mut AppState, _| {
+                    s.engine_plays_black = !s.engine_plays_black;
+                    s.set_player(1, s.engine_plays_black);
+                },
+            ),
+        ))
+        .gap(TINY_GAP),
+        // Engine vs engine games play themselves, with a pause between the moves.
+        (state.players == [PlayerKind::Engine; 2]).then(|| {
+            flex_col((
+                label(format!(
+                    "{:.1} sec between engine moves",
+                    state.move_delay()
+                )),
+                slider(
+                    MIN_MOVE_SECS,
+                    5.0,
+                    state.move_delay(),
+                    |s: &mut AppState, val| {
+                        s.autoplay_delay = val;
+                    },
+                ),
+                text_button(
+                    if state.autoplay_paused {
+                        "Resume autoplay"
+                    } else {
+                        "Pause autoplay"
+                    },
+                    |s: &mut AppState| {
+                        s.autoplay_paused = !s.autoplay_paused;
+                    },
+                ),
+                if state.games_remaining > 0 {
+                    Either::A(text_button("Stop batch", |s: &mut AppState| {
+                        s.games_remaining = 0;
+                    }))
+                } else {
+                    Either::B(flex_row((
+                        text_button(
+                            format!("Play {:.0} games", state.batch_games),
+                            |s: &mut AppState| s.start_batch(),
+                        ),
+                        slider(1.0, 100.0, state.batch_games, |s: &mut AppState, val| {
+                            s.batch_games = val.round();
+                        }),
+                    )))
+                },
+                (state.score != [0; 3] || state.games_remaining > 0).then(|| {
+                    let [white, black, draws] = state.score;
+                    label(format!(
+                        "Batch: White {white}, Black {black}, draws {draws}, {} to play",
+                        state.games_remaining
+                    ))
+                }),
+            ))
+            .cross_axis_alignment(CrossAxisAlignment::Start)
+            .gap(TINY_GAP)
+        }),
+        checkbox(
+            "Step engine moves",
+            state.step_mode,
+            |s: &mut AppState, _| {
+                s.step_mode = !s.step_mode;
+                s.step_pending = false;
+            },
+        ),
+        state.step_mode.then(|| {
+            text_button("Step", |s: &mut AppState| {
+                // A press while no engine move waits would let the next one through unasked.
+                if s.awaiting_step() {
+                    s.step_pending = true;
+                }
+            })
+        }),
+        checkbox(
+            "Evaluation bar",
+            state.show_eval_bar,
+            |s: &mut AppState, _| {
+                s.show_eval_bar = !s.show_eval_bar;
+            },
+        ),
+    ))
+    .cross_axis_alignment(CrossAxisAlignment::Start)
+    .gap(TINY_GAP)
+}
+
+/// Choice between the built-in engine and an external UCI engine.
+fn backend_section(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
+    flex_col((
+        checkbox(
+            "External UCI engine",
+            state.external_engine,
+            |s: &mut AppState, _| {
+                s.external_engine = !s.external_engine;
+            },
+        ),
+        state.external_engine.then(|| {
+            text_input(state.engine_path.clone(), |s: &mut AppState, text| {
+                s.engine_path = text;
+            })
+        }),
+    ))
+    .cross_axis_alignment(CrossAxisAlignment::Start)
+    .gap(TINY_GAP)
+}
+
+fn options_section(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
+    flex_col((
+        checkbox(
+            "Pulse last move",
+            state.pulse_last_move,
+            |s: &mut AppState, _| {
+                s.pulse_last_move = !s.pulse_last_move;
+            },
+        ),
+        checkbox(
+            "Show square control",
+            state.show_control,
+            |s: &mut AppState, _| {
+                s.show_control = !s.show_control;
+            },
+        ),
+        flex_row((
+            checkbox("Blindfold", state.blindfold, |s: &mut AppState, _| {
+                s.blindfold = !s.blindfold;
+            }),
+            state.blindfold.then(|| {
+                checkbox(
+                    "Show last move",
+                    state.blindfold_last_move,
+                    |s: &mut AppState, _| {
+                        s.blindfold_last_move = !s.blindfold_last_move;
+                    },
+                )
+            }),
+        ))
+        .gap(TINY_GAP),
+        flex_row((
+            checkbox(
+                "Speak White's moves",
+                state.speak_moves[0],
+                |s: &mut AppState, _| {
+                    s.speak_moves[0] = !s.speak_moves[0];
+                },
+            ),
+            checkbox(
+                "Speak Black's moves",
+                state.speak_moves[1],
+                |s: &mut AppState, _| {
+                    s.speak_moves[1] = !s.speak_moves[1];
+                },
+            ),
+            checkbox("Mute", state.mute, |s: &mut AppState, _| {
+                s.mute = !s.mute;
+            }),
+        ))
+        .gap(TINY_GAP),
+        checkbox(
+            "Click re-selects own piece",
+            state.reselect_own,
+            |s: &mut AppState, _| {
+                s.reselect_own = !s.reselect_own;
+            },
+        ),
+        checkbox(
+            "Material bar",
+            state.show_material_bar,
+            |s: &mut AppState, _| {
+                s.show_material_bar = !s.show_material_bar;
+            },
+        ),
+        checkbox(
+            "Auto-play forced moves",
+            state.auto_forced,
+            |s: &mut AppState, _| {
+                s.auto_forced = !s.auto_forced;
+            },
+        ),
+        flex_row((
+            checkbox(
+                "Blunder warnings",
+                state.blunder_warnings,
+                |s: &mut AppState, _| {
+                    s.blunder_warnings = !s.blunder_warnings;
+                },
+            ),
+            checkbox("Check assist", state.check_assist, |s: &mut AppState, _| {
+                s.check_assist = !s.check_assist;
+            }),
+        ))
+        .gap(TINY_GAP),
+        checkbox(
+            "Analysis overlay",
+            state.analysis_overlay,
+            |s: &mut AppState, _| {
+                s.analysis_overlay = !s.analysis_overlay;
+            },
+        ),
+        checkbox("Low CPU when idle", state.low_cpu, |s: &mut AppState, _| {
+            s.low_cpu = !s.low_cpu;
+        }),
+        checkbox(
+            "Demo: restart finished games",
+            state.auto_restart,
+            |s: &mut AppState, _| {
+                s.auto_restart = !s.auto_restart;
+            },
+        ),
+        state.auto_restart.then(|| {
+            checkbox(
+                "Demo: swap sides",
+                state.auto_restart_swap,
+                |s: &mut AppState, _| {
+                    s.auto_restart_swap = !s.auto_restart_swap;
+                },
+            )
+        }),
+        checkbox(
+            "Flip after each move",
+            state.flip_each_move,
+            |s: &mut AppState, _| {
+                s.flip_each_move = !s.flip_each_move;
+                if s.flip_each_move {
+                    s.rotated = s.turn == 1;
+                }
+            },
+        ),
+        flex_row((
+            text_button(
+                match state.piece_set {
+                    PieceSet::Unicode => "Pieces: Unicode".to_string(),
+                    PieceSet::Images(set) => format!("Pieces: {}", set.name()),
+                },
+                |s: &mut AppState| {
+                    s.piece_set = match s.piece_set {
+                        PieceSet::Unicode => PieceSet::Images(pieces::ImageSet::Simple),
+                        PieceSet::Images(pieces::ImageSet::Simple) => {
+                            PieceSet::Images(pieces::ImageSet::Geometric)
+                        }
+                        PieceSet::Images(pieces::ImageSet::Geometric) => PieceSet::Unicode,
+                    };
+                },
+            ),
+            label(format!("Size {:.0}%", state.piece_scale * 100.0)),
+            slider(0.5, 1.0, state.piece_scale, |s: &mut AppState, val| {
+                s.piece_scale = val;
+            }),
+        ))
+        .gap(TINY_GAP),
+        (state.piece_set == PieceSet::Unicode).then(|| {
+            flex_row((
+                checkbox(
+                    "Solid glyphs",
+                    state.use_solid_unicode,
+                    |s: &mut AppState, _| {
+                        s.use_solid_unicode = !s.use_solid_unicode;
+                    },
+                ),
+                checkbox(
+                    "Text style",
+                    state.text_presentation,
+                    |s: &mut AppState, _| {
+                        s.text_presentation = !s.text_presentation;
+                    },
+                ),
+            ))
+            .gap(TINY_GAP)
+        }),
+        piece_set_preview(state),
+    ))
+    .cross_axis_alignment(CrossAxisAlignment::Start)
+    .gap(TINY_GAP)
+}
+
+fn game_section(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
+    flex_col((
+        text_button("Rotate", |s: &mut AppState| {
+            s.rotated = !s.rotated;
+        }),
+        flex_row((
+            text_button("New game", |s: &mut AppState| {
+                s.new_game();
+                if s.fair_start && s.players == [PlayerKind::Human; 2] {
+                    // `rotated == false` shows White at the bottom.
+                    s.rotated = random_index(2) == 1;
+                    s.status = if s.rotated {
+                        "Fair start: the player at the top plays White".into()
+                    } else {
+                        "Fair start: the player at the bottom plays White".into()
+                    };
+                }
+            }),
+            checkbox("Fair start", state.fair_start, |s: &mut AppState, _| {
+                s.fair_start = !s.fair_start;
+            }),
+            text_button("Takeback", |s: &mut AppState| s.takeback()),
+            (!state.redo_stack.is_empty())
+                .then(|| text_button("Redo", |s: &mut AppState| s.redo())),
+            (state.phase == Phase::Ready && state.replay.is_none() && state.hint_rx.is_none())
+                .then(|| text_button("Hint", |s: &mut AppState| s.request_hint())),
+            (state.phase == Phase::EnginePlaying).then(|| {
+                text_button("Stop", |s: &mut AppState| {
+                    s.search_stop.store(true, Ordering::Relaxed);
+                })
+            }),
+            // Only while a game is running; a second click within a few seconds confirms.
+            (state.phase != Phase::Inactive && state.replay.is_none() && !state.history.is_empty())
+                .then(|| {
+                    text_button(
+                        if state.resign_pending.is_some() {
+                            "Resign?"
+                        } else {
+                            "Resign"
+                        },
+                        |s: &mut AppState| {
+                            if s.resign_pending.is_some() {
+                                s.resign();
+                            } else {
+                                s.resign_pending = Some(RESIGN_CONFIRM_SECS);
+                            }
+                        },
+                    )
+                }),
+        ))
+        .gap(TINY_GAP),
+        flex_row((
+            label("Move:"),
+            text_input(state.move_input.clone(), |s: &mut AppState, text| {
+                s.move_input = text;
+            })
+            .on_enter(|s: &mut AppState, _| s.enter_move()),
+        ))
+        .gap(TINY_GAP),
+        text_button("Board only", |s: &mut AppState| {
+            s.minimal_view = true;
+        }),
+        text_button(
+            format!("Coordinates: {:?}", state.coords),
+            |s: &mut AppState| {
+                s.coords = match s.coords {
+                    CoordStyle::None => CoordStyle::Outside,
+                    CoordStyle::Outside => CoordStyle::Inside,
+                    CoordStyle::Inside => CoordStyle::Both,
+                    CoordStyle::Both => CoordStyle::None,
+                };
+            },
+        ),
+        flex_row((
+            text_button("<", |s: &mut AppState| {
+                s.opening = (s.opening + OPENINGS.len() - 1) % OPENINGS.len();
+            }),
+            text_button(OPENINGS[state.opening].0, |s: &mut AppState| {
+                let (name, moves) = OPENINGS[s.opening];
+                s.load_opening(name, moves);
+            }),
+            text_button(">", |s: &mut AppState| {
+                s.opening = (s.opening + 1) % OPENINGS.len();
+            }),
+        ))
+        .gap(TINY_GAP),
+        flex_row((
+            checkbox(
+                "Ask for promotion piece",
+                state.ask_promotion,
+                |s: &mut AppState, _| {
+                    s.ask_promotion = !s.ask_promotion;
+                },
+            ),
+            (!state.ask_promotion).then(|| promotion_button(state, 0, "White")),
+            (!state.ask_promotion).then(|| promotion_button(state, 1, "Black")),
+        ))
+        .gap(TINY_GAP),
+        checkbox(
+            "Claim repetition draws",
+            state.claim_draws,
+            |s: &mut AppState, _| {
+                s.claim_draws = !s.claim_draws;
+            },
+        ),
+        text_button("Print movelist", |s: &mut AppState| {
+            let names = s.players.map(|p| format!("{p:?}"));
+            print!("{}", s.game_info.headers(&names[0], &names[1], s.result));
+            if let Some(fen) = &s.start_fen {
+                print!("[SetUp \"1\"]\n[FEN \"{fen}\"]\n");
+            }
+            if let Ok(game) = s.game.lock() {
+                engine::print_move_list(&game);
+            }
+        }),
+    ))
+    .cross_axis_alignment(CrossAxisAlignment::Start)
+    .gap(TINY_GAP)
+}
+
+/// Starts replays of the current game, and pauses or stops a running one.
+fn replay_section(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
+    let controls = match &state.replay {
+        None if state.history.is_empty() => None,
+        None => Some(Either::A(
+            flex_row((
+                text_button("Replay with timing", |s: &mut AppState| {
+                    s.start_replay(false);
+                }),
+                text_button("Auto-advance", |s: &mut AppState| {
+                    s.start_replay(true);
+                }),
+                text_button("First blunder", |s: &mut AppState| {
+                    s.next_blunder();
+                }),
+            ))
+            .gap(TINY_GAP),
+        )),
+        Some(replay) => Some(Either::B(
+            flex_row((
+                text_button(
+                    if replay.paused { "Resume" } else { "Pause" },
+                    |s: &mut AppState| {
+                        if let Some(replay) = &mut s.replay {
+                            replay.paused = !replay.paused;
+                        }
+                    },
+                ),
+                text_button("Stop", |s: &mut AppState| {
+                    s.stop_replay();
+                }),
+                text_button("Next blunder", |s: &mut AppState| {
+                    s.next_blunder();
+                }),
+            ))
+            .gap(TINY_GAP),
+        )),
+    };
+    let fixed_pace = state.replay.as_ref().is_some_and(|r| r.fixed_pace);
+    let speed = (fixed_pace || (state.replay.is_none() && !state.history.is_empty())).then(|| {
+        flex_col((
+            label(format!(
+                "{:.1} sec/move when auto-advancing",
+                state.replay_step
+            )),
+            slider(0.2, 5.0, state.replay_step, |s: &mut AppState, val| {
+                s.replay_step = val;
+            }),
+        ))
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .gap(TINY_GAP)
+    });
+    flex_col((controls, speed))
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .gap(TINY_GAP)
+}
+
+/// Collapsible editor for the board and piece colors, with a contrast check.
+fn theme_section(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
+    let toggle = text_button(
+        if state.show_theme {
+            "Hide colors"
+        } else {
+            "Show colors"
+        },
+        |s: &mut AppState| {
+            s.show_theme = !s.show_theme;
+        },
+    );
+    // Edited colors show as "Custom"; the next click starts over with the first preset.
+    let preset = THEME_PRESETS.iter().position(|&(_, t)| t == state.theme);
+    let presets = text_button(
+        format!("Theme: {}", preset.map_or("Custom", |i| THEME_PRESETS[i].0)),
+        |s: &mut AppState| {
+            let preset = THEME_PRESETS.iter().position(|&(_, t)| t == s.theme);
+            let next = preset.map_or(0, |i| (i + 1) % THEME_PRESETS.len());
+            s.theme = THEME_PRESETS[next].1;
+        },
+    );
+    let dark_mode = checkbox("Dark mode", state.dark_mode, |s: &mut AppState, _| {
+        s.set_dark_mode(!s.dark_mode);
+    });
+    let toggle = flex_row((presets, toggle, dark_mode)).gap(TINY_GAP);
+    let editor = state.show_theme.then(|| {
+        let part = state.theme_part;
+        let (r, g, b) = match part {
+            ThemePart::Light => state.theme.light,
+            ThemePart::Dark => state.theme.dark,
+            ThemePart::Piece => state.theme.piece,
+        };
+        let channel = |name: &str, value: u8, index: usize| {
+            flex_row((
+                sized_box(label(format!("{name} {value}"))).width(MOVE_NUMBER_WIDTH),
+                slider(0.0, 255.0, value as f64, move |s: &mut AppState, v| {
+                    let color = match s.theme_part {
+                        ThemePart::Light => &mut s.theme.light,
+                        ThemePart::Dark => &mut s.theme.dark,
+                        ThemePart::Piece => &mut s.theme.piece,
+                    };
+                    let v = v.round() as u8;
+                    match index {
+                        0 => color.0 = v,
+                        1 => color.1 = v,
+                        _ => color.2 = v,
+                    }
+                }),
+            ))
+            .gap(TINY_GAP)
+        };
+        flex_col((
+            flex_row((
+                text_button(
+                    match part {
+                        ThemePart::Light => "Editing: light squares",
+                        ThemePart::Dark => "Editing: dark squares",
+                        ThemePart::Piece => "Editing: pieces",
+                    },
+                    |s: &mut AppState| {
+                        s.theme_part = match s.theme_part {
+                            ThemePart::Light => ThemePart::Dark,
+                            ThemePart::Dark => ThemePart::Piece,
+                            ThemePart::Piece => ThemePart::Light,
+                        };
+                    },
+                ),
+                text_button("Reset", |s: &mut AppState| {
+                    s.theme = DEFAULT_THEME;
+                }),
+            ))
+            .gap(TINY_GAP),
+            channel("R", r, 0),
+            channel("G", g, 1),
+            channel("B", b, 2),
+            contrast_warning(&state.theme)
+                .map(|text| label(text).color(Color::from_rgb8(200, 0, 0))),
+        ))
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .gap(TINY_GAP)
+    });
+    flex_col((toggle, editor))
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .gap(TINY_GAP)
+}
+
+/// Collapsible legend of the piece values used for the material display.
+fn piece_values_section(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
+    let toggle = text_button(
+        if state.show_piece_values {
+            "Hide piece values"
+        } else {
+            "Show piece values"
+        },
+        |s: &mut AppState| {
+            s.show_piece_values = !s.show_piece_values;
+        },
+    );
+    let editor = state.show_piece_values.then(|| {
+        let pieces = [
+            Piece::Pawn,
+            Piece::Knight,
+            Piece::Bishop,
+            Piece::Rook,
+            Piece::Queen,
+        ];
+        let rows = pieces
+            .map(|piece| {
+                let index = piece as usize;
+                let glyph = ColoredPiece {
+                    piece,
+                    side: Side::White,
+                };
+                flex_row((
+                    sized_box(piece_view(state, Some(glyph), PREVIEW_SIZE as f32))
+                        .width(Length::const_px(PREVIEW_SIZE))
+                        .height(Length::const_px(PREVIEW_SIZE)),
+                    sized_box(label(format!("{}", state.piece_values[index])))
+                        .width(MOVE_NUMBER_WIDTH),
+                    // Quarter pawn steps, so values like 3.25 for the bishop are possible.
+                    slider(
+                        0.25,
+                        12.0,
+                        state.piece_values[index],
+                        move |s: &mut AppState, v| {
+                            s.piece_values[index] = (v * 4.0).round() / 4.0;
+                        },
+                    ),
+                ))
+                .gap(TINY_GAP)
+            })
+            .to_vec();
+        flex_col((
+            flex_col(rows)
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .gap(TINY_GAP),
+            text_button("Reset", |s: &mut AppState| {
+                s.piece_values = DEFAULT_PIECE_VALUES;
+            }),
+        ))
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .gap(TINY_GAP)
+    });
+    flex_col((toggle, editor))
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .gap(TINY_GAP)
+}
+
+/// Collapsible editor for the PGN header fields.
+fn game_info_section(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
+    let toggle = text_button(
+        if state.show_game_info {
+            "Hide game details"
+        } else {
+            "Show game details"
+        },
+        |s: &mut AppState| {
+            s.show_game_info = !s.show_game_info;
+        },
+    );
+    let fields = state.show_game_info.then(|| {
+        let info = &state.game_info;
+        let date_ok = pgn::normalize_date(&info.date).is_some();
+        flex_col((
+            label("Event"),
+            text_input(info.event.clone(), |s: &mut AppState, text| {
+                s.game_info.event = text;
+            }),
+            label("Site"),
+            text_input(info.site.clone(), |s: &mut AppState, text| {
+                s.game_info.site = text;
+            }),
+            label("Round"),
+            text_input(info.round.clone(), |s: &mut AppState, text| {
+                s.game_info.round = text;
+            }),
+            label(if date_ok {
+                "Date"
+            } else {
+                "Date (use YYYY.MM.DD)"
+            }),
+            text_input(info.date.clone(), |s: &mut AppState, text| {
+                s.game_info.date = text;
+            }),
+        ))
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .gap(TINY_GAP)
+    });
+    flex_col((toggle, fields))
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .gap(TINY_GAP)
+}
+
+/// Cycles the piece human pawns of one side promote to.
+fn promotion_button(
+    state: &AppState,
+    side: usize,
+    name: &str,
+) -> impl WidgetView<AppState> + use<> {
+    text_button(
+        format!("{name} promotes: {:?}", state.promotion[side]),
+        move |s: &mut AppState| {
+            s.promotion[side] = match s.promotion[side] {
+                Piece::Queen => Piece::Rook,
+                Piece::Rook => Piece::Bishop,
+                Piece::Bishop => Piece::Knight,
+                _ => Piece::Queen,
+            };
+        },
+    )
+}
+
+/// Answer to the promotion prompt: play the held back pawn move promoting to `piece`.
+fn promotion_choice_button(piece: Piece) -> impl WidgetView<AppState> + use<> {
+    text_button(format!("{piece:?}"), move |s: &mut AppState| {
+        s.pending_move = s.promotion_prompt.take();
+        s.promotion_choice = Some(piece);
+        s.phase = Phase::MoveAttempt;
+    })
+}
+
+/// A single move of the move list; `ply` counts from 0 for White's first move.
+/// Moves are buttons that show the position after them on the board and mark their
+/// squares; a second click returns to the live position.
+fn movelist_cell(state: &AppState, ply: usize) -> impl WidgetView<AppState> + use<> {
+    let cell = match state.movelist.get(ply) {
+        Some(text) => {
+            let background = if state.marked_move == Some(ply) {
+                MARKED_MOVE_COLOR
+            } else {
+                Color::TRANSPARENT
+            };
+            Either::A(
+                button(label(text.clone()), move |s: &mut AppState| {
+                    s.view((s.marked_move != Some(ply)).then_some(ply + 1));
+                })
+                .padding(0.0)
+                .background_color(background),
+            )
         }
-    }
+        None => Either::B(label("")),
+    };
+    sized_box(cell).width(MOVE_WIDTH)
+}
 
-    grid(cells, BOARD_SIZE as i32, BOARD_SIZE as i32)
+/// The move list as aligned rows of move number, white move and black move, below
+/// buttons to step through the positions of the game.
+fn movelist_view(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
+    let shown = state.viewed.map_or(state.history.len(), |(plies, _)| plies);
+    let steps = flex_row((
+        text_button("<", move |s: &mut AppState| {
+            if shown > 0 {
+                s.view(Some(shown - 1));
+            }
+        }),
+        text_button(">", move |s: &mut AppState| {
+            if s.viewed.is_some() {
+                s.view(Some(shown + 1));
+            }
+        }),
+        (state.viewed.is_some() && state.replay.is_none())
+            .then(|| text_button("Play from here", |s: &mut AppState| s.play_from_viewed())),
+    ))
+    .gap(TINY_GAP);
+    let rows = (0..state.movelist.len().div_ceil(2))
+        .map(|n| {
+            flex_row((
+                sized_box(label(format!("{}.", n + 1))).width(MOVE_NUMBER_WIDTH),
+                movelist_cell(state, 2 * n),
+                movelist_cell(state, 2 * n + 1),
+            ))
+            .gap(TINY_GAP)
+        })
+        .collect::<Vec<_>>();
+
+    flex_col((
+        steps,
+        flex_col(rows)
+            .cross_axis_alignment(CrossAxisAlignment::Start)
+            .gap(NO_GAP),
+    ))
+    .cross_axis_alignment(CrossAxisAlignment::Start)
+    .gap(TINY_GAP)
 }
 
-fn settings_panel(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
-    let movelist_text = state.movelist_text();
+/// Pieces each side has captured, as glyphs, with the material lead of the side ahead.
+fn captured_section(state: &AppState) -> impl WidgetView<AppState> + use<> {
+    const PIECES: [Piece; 5] = [
+        Piece::Pawn,
+        Piece::Knight,
+        Piece::Bishop,
+        Piece::Rook,
+        Piece::Queen,
+    ];
+    let lost = captured(&state.board);
+    let balance = material_balance(&state.board, &state.piece_values);
+    let row = |side: Side, lead: f64| {
+        // The pieces taken by `side` are the ones the other side lost.
+        let other = match side {
+            Side::White => Side::Black,
+            Side::Black => Side::White,
+        };
+        let mut text: String = PIECES
+            .iter()
+            .flat_map(|&piece| {
+                let glyph = piece_unicode(ColoredPiece { piece, side: other }, false);
+                std::iter::repeat_n(glyph, lost[other as usize][piece as usize] as usize)
+            })
+            .collect();
+        if lead > 0.0 {
+            text.push_str(&format!(" +{lead}"));
+        }
+        label(text).text_size(18.0)
+    };
+    flex_col((row(Side::White, balance), row(Side::Black, -balance)))
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .gap(NO_GAP)
+}
 
+/// The FEN of the current position, with a button to copy it, and a field to set up
+/// a position from a FEN.
+fn fen_section(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
     flex_col((
-        FlexSpacer::Fixed(GAP),
-        label(&*state.status),
-        FlexSpacer::Fixed(TINY_GAP),
-        label(format!(
-            "White: {}",
-            AppState::formatted_clock(state.time_elapsed[0])
-        )),
-        label(format!(
-            "Black: {}",
-            AppState::formatted_clock(state.time_elapsed[1])
-        )),
-        FlexSpacer::Fixed(TINY_GAP),
-        label(format!("{:.2} sec/move", state.time_per_move)),
-        slider(
-            0.1,
-            5.0,
-            state.time_per_move,
-            |state: &mut AppState, val| {
-                state.time_per_move = val;
+        label(state.fen.clone()).text_size(12.0),
+        text_button("Copy FEN", |s: &mut AppState| {
+            s.status = match clipboard::copy(&s.fen) {
+                Ok(()) => "FEN copied".into(),
+                Err(err) => format!("Copy failed: {err}"),
+            };
+        }),
+        text_input(state.fen_input.clone(), |s: &mut AppState, text| {
+            s.fen_input = text;
+        }),
+        text_button("Set position", |s: &mut AppState| s.set_position()),
+        match &state.editor {
+            None => Either::A(text_button("Edit position", |s: &mut AppState| {
+                s.start_editing()
+            })),
+            Some(editor) => Either::B(editor_controls(editor)),
+        },
+    ))
+    .cross_axis_alignment(CrossAxisAlignment::Start)
+    .gap(TINY_GAP)
+}
+
+/// Side to move and castling rights of the edited position, and the buttons to leave
+/// the editor.
+fn editor_controls(editor: &Editor) -> impl WidgetView<AppState> + use<> {
+    let castling = |name: &'static str, index: usize| {
+        checkbox(name, editor.castling[index], move |s: &mut AppState, _| {
+            if let Some(editor) = &mut s.editor {
+                editor.castling[index] = !editor.castling[index];
+            }
+        })
+    };
+    flex_col((
+        text_button(
+            if editor.turn == 0 {
+                "To move: White"
+            } else {
+                "To move: Black"
             },
-        ),
-        checkbox(
-            "Engine plays white",
-            state.engine_plays_white,
-            |s: &mut AppState, _| {
-                s.engine_plays_white = !s.engine_plays_white;
-                s.players[0] = PLAYER_FOR_ENGINE_FLAG[s.engine_plays_white as usize];
-                s.phase = Phase::Uninitialized;
+            |s: &mut AppState| {
+                if let Some(editor) = &mut s.editor {
+                    editor.turn = 1 - editor.turn;
+                }
             },
         ),
+        flex_row((castling("White O-O", 0), castling("White O-O-O", 1))).gap(TINY_GAP),
+        flex_row((castling("Black O-O", 2), castling("Black O-O-O", 3))).gap(TINY_GAP),
+        flex_row((
+            text_button("Done", |s: &mut AppState| s.finish_editing()),
+            text_button("Cancel", |s: &mut AppState| s.cancel_editing()),
+        ))
+        .gap(TINY_GAP),
+    ))
+    .cross_axis_alignment(CrossAxisAlignment::Start)
+    .gap(TINY_GAP)
+}
+
+/// File name for PGN export and import.
+fn pgn_section(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
+    flex_row((
+        text_input(state.pgn_path.clone(), |s: &mut AppState, text| {
+            s.pgn_path = text;
+        }),
+        text_button("Save PGN", |s: &mut AppState| s.save_pgn()),
+        text_button("Load PGN", |s: &mut AppState| s.load_pgn()),
+    ))
+    .gap(TINY_GAP)
+}
+
+/// Polyglot opening book for the engine.
+fn book_section(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
+    flex_col((
+        flex_row((
+            text_input(state.book_path.clone(), |s: &mut AppState, text| {
+                s.book_path = text;
+            }),
+            text_button("Load book", |s: &mut AppState| s.load_book()),
+        ))
+        .gap(TINY_GAP),
         checkbox(
-            "Engine plays black",
-            state.engine_plays_black,
+            "Engine uses Polyglot book",
+            state.use_polyglot,
             |s: &mut AppState, _| {
-                s.engine_plays_black = !s.engine_plays_black;
-                s.players[1] = PLAYER_FOR_ENGINE_FLAG[s.engine_plays_black as usize];
-                s.phase = Phase::Uninitialized;
+                s.use_polyglot = !s.use_polyglot;
             },
         ),
-        text_button("Rotate", |s: &mut AppState| {
-            s.rotated = !s.rotated;
-        }),
-        text_button("New game", |s: &mut AppState| {
-            if let Ok(mut game) = s.game.lock() {
-                engine::reset_game(&mut game);
-                s.board = engine_to_board(engine::get_board(&game));
-                s.square_tags = [0; 64];
-                s.selected = None;
-                s.pending_move = None;
-                s.rx = None;
-                s.phase = Phase::Uninitialized;
-                s.time_elapsed = [0.0, 0.0];
-                s.movelist.clear();
-            }
-        }),
-        text_button("Print movelist", |s: &mut AppState| {
-            if let Ok(game) = s.game.lock() {
-                engine::print_move_list(&game);
-            }
-        }),
-        sized_box(prose(movelist_text)).width(200_i32.px()),
+    ))
+    .cross_axis_alignment(CrossAxisAlignment::Start)
+    .gap(TINY_GAP)
+}
+
+fn settings_panel(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
+    flex_col((
+        FlexSpacer::Fixed(GAP),
+        clock_section(state),
+        engine_section(state),
+        backend_section(state),
+        options_section(state),
+        game_section(state),
+        game_info_section(state),
+        pgn_section(state),
+        book_section(state),
+        theme_section(state),
+        piece_values_section(state),
+        replay_section(state),
+        captured_section(state),
+        movelist_view(state),
+        fen_section(state),
         FlexSpacer::Fixed(GAP),
     ))
     .cross_axis_alignment(CrossAxisAlignment::Start)
     .gap(GAP)
 }
 
-fn main_layout(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
-    flex_row((
-        FlexSpacer::Fixed(GAP),
-        settings_panel(state),
-        flex_col((
+fn status_bar(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
+    let turn = match state.phase {
+        Phase::Inactive => "Game over",
+        _ if state.turn == 0 => "White to move",
+        _ => "Black to move",
+    };
+
+    sized_box(
+        flex_row((
             FlexSpacer::Fixed(GAP),
-            board_grid(state).flex(1.0),
+            label(&*state.status),
+            (state.phase == Phase::Guessing).then(|| label("Guess the engine's move.")),
+            state.clock_paused.then(|| label("Clocks paused.")),
+            state
+                .awaiting_step()
+                .then(|| label("Press Step for the next engine move.")),
+            state.autoplay_held().then(|| label("Autoplay paused.")),
+            state.blunder_prompt.map(|_| {
+                flex_row((
+                    text_button("Play anyway", |s: &mut AppState| {
+                        s.pending_move = s.blunder_prompt.take();
+                        s.blunder_confirmed = true;
+                        s.phase = Phase::MoveAttempt;
+                    }),
+                    text_button("Cancel", |s: &mut AppState| {
+                        s.blunder_prompt = None;
+                        s.promotion_choice = None;
+                        s.status = "Move taken back.".into();
+                    }),
+                ))
+                .gap(TINY_GAP)
+            }),
+            (state.claim_draws && state.phase != Phase::Inactive && state.repetitions() >= 3).then(
+                || {
+                    text_button("Claim draw", |s: &mut AppState| {
+                        s.declare_draw("Draw by threefold repetition");
+                    })
+                },
+            ),
+            state.promotion_prompt.map(|_| {
+                flex_row((
+                    promotion_choice_button(Piece::Queen),
+                    promotion_choice_button(Piece::Rook),
+                    promotion_choice_button(Piece::Bishop),
+                    promotion_choice_button(Piece::Knight),
+                    text_button("Cancel", |s: &mut AppState| {
+                        s.promotion_prompt = None;
+                        s.status = "Move taken back.".into();
+                    }),
+                ))
+                .gap(TINY_GAP)
+            }),
+            FlexSpacer::Flex(1.0),
+            state
+                .restart_countdown
+                .map(|secs| label(format!("New game in {:.0} s", secs.ceil()))),
+            // Development builds only: flag clock attribution bugs.
+            (cfg!(debug_assertions) && state.clock_fault)
+                .then(|| label("clock fault").color(Color::from_rgb8(200, 0, 0))),
+            label(turn),
             FlexSpacer::Fixed(GAP),
         ))
-        .flex(1.0),
+        .gap(GAP),
+    )
+    .height(STATUS_BAR_HEIGHT)
+}
+
+/// The board, kept square and centered in whatever space the layout leaves for it.
+fn board_area(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
+    let outside = matches!(state.coords, CoordStyle::Outside | CoordStyle::Both);
+    let margin = if outside { COORD_MARGIN } else { 0.0 };
+    let bar = if state.show_material_bar {
+        MATERIAL_BAR_WIDTH + MATERIAL_BAR_GAP
+    } else {
+        0.0
+    };
+    let eval_column = if state.show_eval_bar {
+        EVAL_COLUMN_WIDTH + MATERIAL_BAR_GAP
+    } else {
+        0.0
+    };
+    let side = (state.board_side - margin - bar - eval_column).max(0.0);
+    let board = sized_box(board_grid(state, side / BOARD_SIZE as f64))
+        .width(Length::const_px(side))
+        .height(Length::const_px(side));
+    let board = zstack((board, result_banner(state)));
+
+    let content = if outside {
+        // Rank strip on the left, file strip below, each cell as large as a square.
+        let square = Length::const_px(side / BOARD_SIZE as f64);
+        let margin = Length::const_px(COORD_MARGIN);
+        let color = coord_color(state.dark_mode);
+        let ranks = (0..BOARD_SIZE)
+            .map(|r| coord_label(rank_at(r, state.rotated), color, margin, square))
+            .collect::<Vec<_>>();
+        let files = (0..BOARD_SIZE)
+            .map(|c| coord_label(file_at(c, state.rotated), color, square, margin))
+            .collect::<Vec<_>>();
+        Either::A(
+            flex_col((
+                flex_row((flex_col(ranks).gap(NO_GAP), board)).gap(NO_GAP),
+                flex_row((FlexSpacer::Fixed(margin), flex_row(files).gap(NO_GAP))).gap(NO_GAP),
+            ))
+            .gap(NO_GAP),
+        )
+    } else {
+        Either::B(board)
+    };
+
+    let eval = state.show_eval_bar.then(|| eval_bar(state, side));
+    let thermometer = state.show_material_bar.then(|| material_bar(state, side));
+    let content = flex_row((eval, content, thermometer))
+        // Top aligned, so the bar stays level with the board above the file strip.
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .gap(Length::const_px(MATERIAL_BAR_GAP));
+
+    resize_observer(
+        |s: &mut AppState, size| {
+            s.board_side = size.width.min(size.height).floor();
+        },
+        flex_col((content,))
+            .main_axis_alignment(MainAxisAlignment::Center)
+            .cross_axis_alignment(CrossAxisAlignment::Center),
+    )
+}
+
+/// The result and how the game ended, in the middle of the board once the game is over.
+fn result_banner(state: &AppState) -> Option<impl WidgetView<AppState> + use<>> {
+    let over = state.phase == Phase::Inactive && state.result != "*" && state.editor.is_none();
+    over.then(|| {
+        let result = match state.result {
+            "1/2-1/2" => "½-½",
+            result => result,
+        };
+        flex_col((
+            label(result).text_size(48.0),
+            label(state.end_reason.clone()),
+        ))
+        .padding(12.0)
+        .corner_radius(8.0)
+        .background_color(RESULT_BANNER_COLOR)
+    })
+}
+
+/// Vertical bar of the board's height, split in White's and Black's share of the material;
+/// each side's share is on its side of the board.
+fn material_bar(state: &AppState, height: f64) -> impl WidgetView<AppState> + use<> {
+    let [white, black] = material(&state.board, &state.piece_values);
+    let white_share = if white + black > 0.0 {
+        white / (white + black)
+    } else {
+        0.5
+    };
+    let part = |share: f64, color: Color| {
+        sized_box(flex_col(()).background_color(color))
+            .width(Length::const_px(MATERIAL_BAR_WIDTH))
+            .height(Length::const_px(height * share))
+    };
+    let white_part = part(white_share, Color::from_rgb8(240, 240, 240));
+    let black_part = part(1.0 - white_share, Color::from_rgb8(40, 40, 40));
+    // `rotated == false` shows White at the bottom.
+    if state.rotated {
+        flex_col((white_part, black_part)).gap(NO_GAP)
+    } else {
+        flex_col((black_part, white_part)).gap(NO_GAP)
+    }
+}
+
+/// Vertical bar of the board's height, split by the evaluation mapped through a logistic
+/// curve, with the score in pawns below it. Mate scores fill the bar.
+fn eval_bar(state: &AppState, height: f64) -> impl WidgetView<AppState> + use<> {
+    let mate = state.eval.abs() > engine::KING_VALUE_DIV_2 as i64;
+    let white_share = if mate {
+        if state.eval > 0 { 1.0 } else { 0.0 }
+    } else {
+        1.0 / (1.0 + 10f64.powf(-state.eval as f64 / EVAL_SCALE_CP))
+    };
+    let text = match state.eval {
+        _ if mate && state.eval > 0 => "+M".to_string(),
+        _ if mate => "-M".to_string(),
+        cp => format!("{:+.1}", cp as f64 / 100.0),
+    };
+    let part = |share: f64, color: Color| {
+        sized_box(flex_col(()).background_color(color))
+            .width(Length::const_px(EVAL_BAR_WIDTH))
+            .height(Length::const_px(
+                (height - EVAL_LABEL_HEIGHT).max(0.0) * share,
+            ))
+    };
+    let white_part = part(white_share, Color::from_rgb8(240, 240, 240));
+    let black_part = part(1.0 - white_share, Color::from_rgb8(40, 40, 40));
+    // `rotated == false` shows White at the bottom.
+    let bar = if state.rotated {
+        flex_col((white_part, black_part)).gap(NO_GAP)
+    } else {
+        flex_col((black_part, white_part)).gap(NO_GAP)
+    };
+    sized_box(
+        flex_col((
+            bar,
+            sized_box(label(text).text_size(11.0)).height(Length::const_px(EVAL_LABEL_HEIGHT)),
+        ))
+        .cross_axis_alignment(CrossAxisAlignment::Center)
+        .gap(NO_GAP),
+    )
+    .width(Length::const_px(EVAL_COLUMN_WIDTH))
+}
+
+/// Color of the board coordinates, readable on the window background.
+fn coord_color(dark_mode: bool) -> Color {
+    if dark_mode {
+        DARK_COORD_COLOR
+    } else {
+        COORD_COLOR
+    }
+}
+
+/// A board coordinate, centered in a cell of the given size.
+fn coord_label(
+    text: char,
+    color: Color,
+    width: Length,
+    height: Length,
+) -> impl WidgetView<AppState> + use<> {
+    sized_box(
+        flex_col((label(text.to_string()).color(color),))
+            .main_axis_alignment(MainAxisAlignment::Center)
+            .cross_axis_alignment(CrossAxisAlignment::Center),
+    )
+    .width(width)
+    .height(height)
+}
+
+fn main_layout(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
+    let content = if state.minimal_view {
+        // The board takes all the space; a small button brings the panel back.
+        Either::A(
+            flex_col((
+                flex_row((
+                    FlexSpacer::Flex(1.0),
+                    text_button("Show panel", |s: &mut AppState| {
+                        s.minimal_view = false;
+                    }),
+                )),
+                board_area(state).flex(1.0),
+            ))
+            .gap(TINY_GAP),
+        )
+    } else {
+        Either::B(
+            flex_row((
+                FlexSpacer::Fixed(GAP),
+                settings_panel(state),
+                flex_col((
+                    FlexSpacer::Fixed(GAP),
+                    board_area(state).flex(1.0),
+                    FlexSpacer::Fixed(GAP),
+                ))
+                .flex(1.0),
+                FlexSpacer::Fixed(GAP),
+            ))
+            // Fill, so the board area gets the full height to fit its square into.
+            .cross_axis_alignment(CrossAxisAlignment::Fill)
+            .gap(GAP),
+        )
+    };
+
+    flex_col((
+        content.flex(1.0),
+        // Fixed height, so the board keeps its share of the window.
+        status_bar(state),
+    ))
+    .background_color(if state.dark_mode {
+        DARK_BACKGROUND
+    } else {
+        Color::TRANSPARENT
+    })
+}
+
+/// All open games; each tab is an independent `AppState` with its own engine and clocks.
+struct App {
+    sessions: Vec<AppState>,
+    /// Index of the visible tab.
+    current: usize,
+    /// Preferences as last loaded or saved; new tabs start with them.
+    settings: settings::Settings,
+    /// Inner size of the window in logical pixels, once known.
+    window_size: Option<[f64; 2]>,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        let settings = settings::load();
+        let mut first = AppState::default();
+        first.apply_settings(&settings);
+        // Shown once at startup; the next move replaces it.
+        if cfg!(debug_assertions) {
+            first.status = "Debug build: the engine is much weaker, use --release".into();
+        }
+        Self {
+            sessions: vec![first],
+            current: 0,
+            window_size: settings.window_size,
+            settings,
+        }
+    }
+}
+
+impl App {
+    fn current_mut(&mut self) -> &mut AppState {
+        &mut self.sessions[self.current]
+    }
+
+    /// Advance all sessions, so background games keep playing while hidden.
+    fn tick(&mut self) {
+        for session in self.sessions.iter_mut().filter(|s| s.active) {
+            session.tick();
+        }
+        // Xilem does not report window focus to views, so we throttle on idleness instead.
+        let idle = self.sessions.iter().all(AppState::is_idle);
+        let ms = if idle { IDLE_TICK_MS } else { TIMER_TICK_MS };
+        TICK_MS.store(ms, Ordering::Relaxed);
+
+        // Save the visible tab's preferences and the window size whenever they change.
+        let settings = settings::Settings {
+            window_size: self.window_size,
+            ..self.sessions[self.current].settings()
+        };
+        if settings != self.settings {
+            if let Err(err) = settings::save(&settings) {
+                eprintln!("Warning: cannot save settings: {err}");
+            }
+            self.settings = settings;
+        }
+    }
+}
+
+fn tab_bar(app: &mut App) -> impl WidgetView<App> + use<> {
+    let tabs = (0..app.sessions.len())
+        .map(|i| {
+            let title = if i == app.current {
+                format!("[Game {}]", i + 1)
+            } else {
+                format!("Game {}", i + 1)
+            };
+            text_button(title, move |app: &mut App| {
+                app.current = i;
+            })
+        })
+        .collect::<Vec<_>>();
+
+    flex_row((
         FlexSpacer::Fixed(GAP),
+        tabs,
+        (app.sessions.len() < MAX_SESSIONS).then(|| {
+            text_button("+", |app: &mut App| {
+                let mut session = AppState::default();
+                session.apply_settings(&app.settings);
+                app.sessions.push(session);
+                app.current = app.sessions.len() - 1;
+            })
+        }),
+        (app.sessions.len() > 1).then(|| {
+            text_button("Close tab", |app: &mut App| {
+                // Dropping the session also drops its receiver; a running engine
+                // thread finishes its search and its send fails silently.
+                app.sessions.remove(app.current);
+                app.current = app.current.min(app.sessions.len() - 1);
+            })
+        }),
     ))
-    .cross_axis_alignment(CrossAxisAlignment::Start)
-    .gap(GAP)
+    .gap(TINY_GAP)
 }
 
-fn app_logic(state: &mut AppState) -> impl WidgetView<AppState> + use<> {
+fn app_logic(app: &mut App) -> impl WidgetView<App> + use<> {
     fork(
-        main_layout(state),
-        state.active.then(|| {
+        resize_observer(
+            |app: &mut App, size| {
+                app.window_size = Some([size.width, size.height]);
+            },
+            flex_col((
+                FlexSpacer::Fixed(TINY_GAP),
+                (!app.sessions[app.current].minimal_view).then(|| tab_bar(app)),
+                lens(main_layout, App::current_mut).flex(1.0),
+            )),
+        ),
+        app.sessions.iter().any(|s| s.active).then(|| {
             task(
                 |proxy, _| async move {
-                    let mut interval = time::interval(Duration::from_millis(TIMER_TICK_MS));
                     while proxy.message(()).is_ok() {
-                        interval.tick().await;
+                        let ms = TICK_MS.load(Ordering::Relaxed);
+                        time::sleep(Duration::from_millis(ms)).await;
                     }
                 },
-                |s: &mut AppState, _| {
-                    s.tick();
+                |app: &mut App, _| {
+                    app.tick();
                 },
             )
         }),
@@ -529,19 +4329,515 @@ const NOTO_SANS_SYMBOLS: &[u8] = include_bytes!(concat!(
     "NotoSansSymbols2-Regular.ttf"
 ));
 
+#[cfg(not(feature = "useSystemFont"))]
+const BUNDLED_FONT_FAMILY: &str = "Noto Sans Symbols 2";
+
+/// Font family stack for the piece glyphs, decided once at startup.
+#[cfg(not(feature = "useSystemFont"))]
+static FONT_FAMILY: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+#[cfg(not(feature = "useSystemFont"))]
+fn font_family() -> &'static str {
+    FONT_FAMILY
+        .get()
+        .map_or(BUNDLED_FONT_FAMILY, String::as_str)
+}
+
+/// How far the chess glyphs of the bundled font sit above the middle of a text line, in em:
+/// half of ascent (1.069) minus descent (0.630), less the mean center of the glyphs (0.365).
+/// The glyph centers differ by at most 0.02 em, so one value serves all pieces.
+/// Custom fonts are drawn unadjusted.
+#[cfg(not(feature = "useSystemFont"))]
+fn glyph_raise_em() -> f64 {
+    if FONT_FAMILY.get().is_none() {
+        0.145
+    } else {
+        0.0
+    }
+}
+
+#[cfg(feature = "useSystemFont")]
+fn glyph_raise_em() -> f64 {
+    0.0
+}
+
+/// TrueType, OpenType or font collection magic number.
+#[cfg(not(feature = "useSystemFont"))]
+fn is_font_data(bytes: &[u8]) -> bool {
+    matches!(
+        bytes.get(0..4),
+        Some(&[0, 1, 0, 0]) | Some(b"OTTO") | Some(b"true") | Some(b"ttcf")
+    )
+}
+
+/// The font file at `path`, or why it cannot be used.
+#[cfg(not(feature = "useSystemFont"))]
+fn read_font(path: &std::path::Path) -> Result<Vec<u8>, String> {
+    let bytes =
+        std::fs::read(path).map_err(|err| format!("cannot read font {}: {err}", path.display()))?;
+    if is_font_data(&bytes) {
+        Ok(bytes)
+    } else {
+        Err(format!("{} is not a font file", path.display()))
+    }
+}
+
+/// Optional user font from `XILEM_CHESS_FONT` (file path) and `XILEM_CHESS_FONT_FAMILY`.
+/// Any problem is reported as a warning and the bundled font is used instead.
+#[cfg(not(feature = "useSystemFont"))]
+fn custom_font() -> Option<(Vec<u8>, String)> {
+    let path = std::path::PathBuf::from(std::env::var_os("XILEM_CHESS_FONT")?);
+    let Ok(family) = std::env::var("XILEM_CHESS_FONT_FAMILY") else {
+        eprintln!("Warning: XILEM_CHESS_FONT_FAMILY not set, using the bundled font");
+        return None;
+    };
+    match read_font(&path) {
+        Ok(bytes) => Some((bytes, family)),
+        Err(err) => {
+            eprintln!("Warning: {err}, using the bundled font");
+            None
+        }
+    }
+}
+
 fn run(event_loop: EventLoopBuilder) -> Result<(), EventLoopError> {
+    let state = App::default();
+    // The last window size, but never below the minimum size.
+    let [width, height] = state.window_size.unwrap_or(INITIAL_WINDOW_SIZE);
+    let [min_width, min_height] = MIN_WINDOW_SIZE;
     let app = Xilem::new_simple(
-        AppState::default(),
+        state,
         app_logic,
         WindowOptions::new("Xilem Chess GUI")
-            .with_min_inner_size(LogicalSize::new(800.0, 800.0))
-            .with_initial_inner_size(LogicalSize::new(1200.0, 1000.0)),
+            .with_min_inner_size(LogicalSize::new(min_width, min_height))
+            .with_initial_inner_size(LogicalSize::new(
+                width.max(min_width),
+                height.max(min_height),
+            )),
     );
     #[cfg(not(feature = "useSystemFont"))]
     let app = app.with_font(Blob::new(Arc::new(NOTO_SANS_SYMBOLS)));
+    // The bundled font stays registered as fallback for glyphs the custom font lacks.
+    #[cfg(not(feature = "useSystemFont"))]
+    let app = match custom_font() {
+        Some((bytes, family)) => {
+            let _ = FONT_FAMILY.set(format!("{family}, {BUNDLED_FONT_FAMILY}"));
+            app.with_font(Blob::new(Arc::new(bytes)))
+        }
+        None => app,
+    };
     app.run_in(event_loop)
 }
 
 fn main() -> Result<(), EventLoopError> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("--match") {
+        let parsed = match &args[1..] {
+            [games, a, b] => games
+                .parse::<u32>()
+                .ok()
+                .zip(arena::EngineConfig::parse(a))
+                .zip(arena::EngineConfig::parse(b)),
+            _ => None,
+        };
+        match parsed {
+            Some(((games, a), b)) => arena::run_match(games, a, b),
+            None => {
+                eprintln!("Usage: xilem-chess --match GAMES SECS[:AGGRESSION] SECS[:AGGRESSION]")
+            }
+        }
+        return Ok(());
+    }
+    if args.first().map(String::as_str) == Some("--stdin") {
+        let secs = args.get(1).map_or(Some(1.0), |s| {
+            s.parse::<f32>().ok().filter(|t| (0.1..18.0).contains(t))
+        });
+        match secs {
+            Some(secs) => script::run(secs),
+            None => eprintln!("Usage: xilem-chess --stdin [SECS]"),
+        }
+        return Ok(());
+    }
     run(EventLoop::with_user_event())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A silent game between `players`; the engine searches to a fixed, shallow depth.
+    fn test_state(players: [PlayerKind; 2]) -> AppState {
+        AppState {
+            players,
+            mute: true,
+            depth_mode: true,
+            search_depth: 2.0,
+            ..Default::default()
+        }
+    }
+
+    /// Tick until `done` holds, failing after ten seconds.
+    fn tick_until(state: &mut AppState, done: impl Fn(&AppState) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !done(state) {
+            assert!(Instant::now() < deadline, "stuck in {:?}", state.phase);
+            state.tick();
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    /// Let the human to move play `mv`, given like "e2e4", as a click on the board would.
+    fn play(state: &mut AppState, mv: &str) {
+        tick_until(state, |s| s.phase == Phase::Ready);
+        state.pending_move = coordinate_move(mv);
+        state.phase = Phase::MoveAttempt;
+        tick_until(state, |s| s.phase != Phase::MoveAttempt);
+    }
+
+    fn side_to_move(state: &AppState) -> usize {
+        engine::turn(&state.game.lock().unwrap())
+    }
+
+    #[test]
+    fn no_search_in_a_checkmate_position() {
+        let mut state = test_state([PlayerKind::Engine, PlayerKind::Human]);
+        // Fool's mate, white to move
+        state.fen_input = "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3".into();
+        state.set_position();
+        assert_eq!(
+            engine::game_state(&mut state.game.lock().unwrap()),
+            engine::STATE_CHECKMATE
+        );
+        tick_until(&mut state, |s| s.phase == Phase::Inactive);
+        assert_eq!(state.result, "0-1");
+        assert!(state.rx.is_none());
+        assert!(state.search_started.is_none());
+        assert!(state.history.is_empty());
+    }
+
+    #[cfg(not(feature = "useSystemFont"))]
+    #[test]
+    fn unusable_font_files_fall_back() {
+        let dir = std::env::temp_dir();
+        assert!(read_font(&dir.join("xilem-chess-no-such-font.ttf")).is_err());
+
+        let junk = dir.join(format!("xilem-chess-junk-{}.ttf", std::process::id()));
+        std::fs::write(&junk, "not a font").unwrap();
+        let result = read_font(&junk);
+        let _ = std::fs::remove_file(&junk);
+        assert!(result.is_err());
+
+        assert!(is_font_data(NOTO_SANS_SYMBOLS));
+    }
+
+    #[test]
+    fn takeback_returns_the_move_to_its_side() {
+        let mut state = test_state([PlayerKind::Human; 2]);
+        play(&mut state, "e2e4");
+        play(&mut state, "e7e5");
+        state.takeback();
+        assert_eq!(side_to_move(&state), 1);
+        tick_until(&mut state, |s| s.phase == Phase::Ready);
+        assert_eq!(state.turn, 1);
+        state.takeback();
+        assert_eq!(side_to_move(&state), 0);
+
+        // Against the engine, its reply is taken back together with the human's move.
+        let mut state = test_state([PlayerKind::Human, PlayerKind::Engine]);
+        play(&mut state, "e2e4");
+        tick_until(&mut state, |s| s.phase == Phase::Ready);
+        state.takeback();
+        assert!(state.history.is_empty());
+        assert_eq!(side_to_move(&state), 0);
+        tick_until(&mut state, |s| s.phase == Phase::Ready);
+        assert_eq!(state.turn, 0);
+    }
+
+    #[test]
+    fn engine_under_promotes() {
+        let mut state = test_state([PlayerKind::Engine, PlayerKind::Human]);
+        // f8=N is mate, a queen on f8 is not even check
+        state.fen_input = "6br/5Ppk/4q1pp/8/8/8/8/K7 w - - 0 1".into();
+        state.set_position();
+        tick_until(&mut state, |s| !s.history.is_empty());
+        assert_eq!(state.history[0].promote_to, 2);
+        let f8 = square_index("f8").unwrap();
+        assert_eq!(engine::get_board(&state.game.lock().unwrap())[f8], 2);
+    }
+
+    #[test]
+    fn blunder_check_holds_back_a_losing_move() {
+        let mut state = test_state([PlayerKind::Human; 2]);
+        state.blunder_warnings = true;
+        state.fen_input = "4k3/8/2n5/8/8/8/8/3QK3 w - - 0 1".into();
+        state.set_position();
+        play(&mut state, "d1d4");
+        assert_eq!(state.blunder_prompt, coordinate_move("d1d4"));
+        assert!(state.history.is_empty());
+        play(&mut state, "d1d3");
+        assert_eq!(state.blunder_prompt, None);
+        assert_eq!(state.history.len(), 1);
+    }
+
+    #[test]
+    fn engine_move_is_dropped_for_a_side_switched_to_human() {
+        let mut state = test_state([PlayerKind::Engine, PlayerKind::Human]);
+        state.depth_mode = false;
+        state.time_per_move = 10.0;
+        tick_until(&mut state, |s| s.phase == Phase::EnginePlaying);
+        // Let the search thread take the game lock.
+        thread::sleep(Duration::from_millis(100));
+        let started = Instant::now();
+        state.set_player(0, false);
+        assert_eq!(state.status, "Engine move discarded");
+        state.tick();
+        assert_eq!(state.phase, Phase::Ready);
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(state.turn, 0);
+        assert!(state.history.is_empty());
+        assert_eq!(
+            engine::position_key(&mut state.game.lock().unwrap()),
+            engine::position_key(&mut engine::new_position())
+        );
+    }
+
+    #[test]
+    fn takeback_and_redo_restore_the_position() {
+        let mut state = test_state([PlayerKind::Human; 2]);
+        state.ask_promotion = false;
+        state.fen_input = "rn2k2r/P7/8/8/4p3/8/3P4/R3K2R w KQk - 0 1".into();
+        state.set_position();
+        let position = |state: &AppState| {
+            let mut game = state.game.lock().unwrap();
+            (engine::get_board(&game), engine::position_key(&mut game))
+        };
+        // double step, en passant, both castlings, capture with promotion
+        let moves = ["d2d4", "e4d3", "e1c1", "e8g8", "a7b8"];
+        let mut before = Vec::new();
+        for mv in moves {
+            before.push(position(&state));
+            play(&mut state, mv);
+        }
+        assert_eq!(state.history.len(), moves.len());
+        let end = position(&state);
+        while let Some(expected) = before.pop() {
+            state.takeback();
+            assert_eq!(position(&state), expected);
+        }
+        for _ in moves {
+            state.redo();
+        }
+        assert_eq!(position(&state), end);
+    }
+
+    #[test]
+    fn castling_tags_king_and_rook() {
+        // king move, rook move
+        let castlings = [
+            ("w", "e1g1", "h1f1"),
+            ("w", "e1c1", "a1d1"),
+            ("b", "e8g8", "h8f8"),
+            ("b", "e8c8", "a8d8"),
+        ];
+        for (side, king, rook) in castlings {
+            let fen = format!("r3k2r/8/8/8/8/8/8/R3K2R {side} KQkq - 0 1");
+            let mut game = engine::from_fen(&fen).unwrap();
+            let (from, to) = coordinate_move(king).unwrap();
+            let flag = engine::do_move(&mut game, from as i8, to as i8, false);
+            assert_eq!(flag, engine::FLAG_CASTLING, "{king}");
+            let (rook_from, rook_to) = coordinate_move(rook).unwrap();
+            assert_eq!(
+                engine::castling_rook(from as i8, to as i8),
+                (rook_from as i8, rook_to as i8),
+                "{king}"
+            );
+            let tagged: Vec<usize> = (0..64)
+                .filter(|&sq| last_move_tags(from, to, flag)[sq] != 0)
+                .collect();
+            let mut expected = vec![from, to, rook_from, rook_to];
+            expected.sort();
+            assert_eq!(tagged, expected, "{king}");
+        }
+    }
+
+    #[test]
+    fn no_book_moves_in_a_set_up_position() {
+        let mut state = test_state([PlayerKind::Engine, PlayerKind::Human]);
+        state.use_book = true;
+        tick_until(&mut state, |s| !s.history.is_empty());
+        assert!(state.book_line.is_some());
+
+        let mut state = test_state([PlayerKind::Engine, PlayerKind::Human]);
+        state.use_book = true;
+        state.fen_input = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".into();
+        state.set_position();
+        tick_until(&mut state, |s| !s.history.is_empty());
+        assert!(state.book_line.is_none());
+    }
+
+    #[test]
+    fn dark_mode_off_restores_the_theme() {
+        let mut state = test_state([PlayerKind::Human; 2]);
+        let brown = THEME_PRESETS[1].1;
+        state.theme = brown;
+        state.set_dark_mode(true);
+        assert_eq!(state.theme, DARK_THEME);
+        state.set_dark_mode(false);
+        assert_eq!(state.theme, brown);
+    }
+
+    #[test]
+    fn viewing_a_ply_shows_its_position() {
+        let mut state = test_state([PlayerKind::Human; 2]);
+        for mv in ["e2e4", "e7e5", "g1f3"] {
+            play(&mut state, mv);
+        }
+        let live = state.board;
+        state.view(Some(1));
+        let (plies, board) = state.viewed.unwrap();
+        assert_eq!(plies, 1);
+        assert_eq!(state.marked_move, Some(0));
+        // BoardView has no PartialEq; its Debug text tells the boards apart.
+        let text = |board: BoardView| format!("{board:?}");
+        let after_e4 = replayed_game(None, &state.history[..1]);
+        assert_eq!(
+            text(board),
+            text(engine_to_board(engine::get_board(&after_e4)))
+        );
+        assert_ne!(text(board), text(live));
+
+        state.view(Some(state.history.len()));
+        assert!(state.viewed.is_none());
+        state.view(None);
+        assert_eq!(state.marked_move, None);
+    }
+
+    #[test]
+    fn engine_plays_from_a_polyglot_book() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let key = openings::key(start).unwrap();
+        // e2e4, with the squares counted from a1 = 0
+        let mv: u16 = 12 << 6 | 28;
+        let entry = [
+            &key.to_be_bytes()[..],
+            &mv.to_be_bytes(),
+            &1u16.to_be_bytes(),
+            &[0; 4],
+        ];
+        let path = std::env::temp_dir().join(format!("xilem-chess-{}.bin", std::process::id()));
+        std::fs::write(&path, entry.concat()).unwrap();
+
+        let mut state = test_state([PlayerKind::Engine, PlayerKind::Human]);
+        state.book_path = path.display().to_string();
+        state.load_book();
+        std::fs::remove_file(&path).unwrap();
+        assert!(state.use_polyglot, "{}", state.status);
+        tick_until(&mut state, |s| !s.history.is_empty());
+        let first = &state.history[0];
+        assert_eq!(Some((first.from, first.to)), coordinate_move("e2e4"));
+        assert!(state.status.ends_with("(book)"), "{}", state.status);
+    }
+
+    #[test]
+    fn blunder_review_numbers_moves_from_the_start_position() {
+        let mut state = test_state([PlayerKind::Human; 2]);
+        state.fen_input = "3qk3/8/8/8/8/8/8/3QK3 b - - 0 1".into();
+        state.set_position();
+        play(&mut state, "d8d2");
+        play(&mut state, "d1d2");
+        let review_done = |s: &AppState| s.replay.as_ref().is_some_and(|r| !r.seeking);
+        state.next_blunder();
+        tick_until(&mut state, review_done);
+        assert!(state.status.starts_with("1.… d8-d2"), "{}", state.status);
+        state.next_blunder();
+        tick_until(&mut state, review_done);
+        assert_eq!(state.status, "No further blunders");
+    }
+
+    #[test]
+    fn engine_replies_to_a_human_move() {
+        let mut state = test_state([PlayerKind::Human, PlayerKind::Engine]);
+        play(&mut state, "e2e4");
+        tick_until(&mut state, |s| s.phase == Phase::Ready);
+        assert_eq!(state.history.len(), 2);
+        assert_eq!(state.movelist.len(), 2);
+        assert_eq!(state.turn, 0);
+        assert_eq!(side_to_move(&state), 0);
+        let replayed = replayed_game(None, &state.history);
+        assert_eq!(
+            engine::get_board(&state.game.lock().unwrap()),
+            engine::get_board(&replayed)
+        );
+        assert_eq!(state.fen, engine::to_fen(&replayed));
+    }
+
+    #[test]
+    fn takeback_and_new_game_stop_a_running_search() {
+        let mut state = test_state([PlayerKind::Human, PlayerKind::Engine]);
+        state.depth_mode = false;
+        state.time_per_move = 10.0;
+        for restart in [AppState::takeback, AppState::new_game] {
+            play(&mut state, "e2e4");
+            tick_until(&mut state, |s| s.phase == Phase::EnginePlaying);
+            // Let the search thread take the game lock.
+            thread::sleep(Duration::from_millis(100));
+            let started = Instant::now();
+            restart(&mut state);
+            assert!(started.elapsed() < Duration::from_secs(2));
+            assert!(state.history.is_empty());
+        }
+    }
+
+    #[test]
+    fn engine_checkmate_ends_the_game() {
+        let mut state = test_state([PlayerKind::Engine, PlayerKind::Human]);
+        state.fen_input = "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1".into();
+        state.set_position();
+        tick_until(&mut state, |s| s.phase == Phase::Inactive);
+        assert_eq!(state.result, "1-0");
+        assert_eq!(state.end_reason, "Checkmate — White wins");
+        assert!(state.movelist[0].ends_with('#'), "{}", state.movelist[0]);
+        state.new_game();
+        assert!(state.end_reason.is_empty());
+    }
+
+    #[test]
+    fn clock_of_the_wrong_side_is_a_fault() {
+        let mut state = test_state([PlayerKind::Engine, PlayerKind::Human]);
+        tick_until(&mut state, |s| s.phase == Phase::Ready);
+        assert!(!state.clock_fault);
+
+        let mut state = test_state([PlayerKind::Engine, PlayerKind::Human]);
+        tick_until(&mut state, |s| s.phase == Phase::EngineThinking);
+        state.turn = 1;
+        state.tick();
+        assert!(state.clock_fault);
+    }
+
+    #[test]
+    fn en_passant_square_without_capture_does_not_break_repetition() {
+        let mut state = test_state([PlayerKind::Human; 2]);
+        // The position after 1.e4 e5 has "e6" in its FEN, but no pawn can take there.
+        for mv in [
+            "e2e4", "e7e5", "g1f3", "b8c6", "f3g1", "c6b8", "g1f3", "b8c6", "f3g1", "c6b8",
+        ] {
+            play(&mut state, mv);
+        }
+        assert_eq!(state.repetitions(), 3);
+        assert_eq!(state.draw_reason(), Some("Draw by threefold repetition"));
+    }
+
+    #[test]
+    fn hint_search_stops_with_a_new_game() {
+        let mut state = test_state([PlayerKind::Human; 2]);
+        tick_until(&mut state, |s| s.phase == Phase::Ready);
+        state.request_hint();
+        tick_until(&mut state, |s| s.hint.is_some());
+
+        state.request_hint();
+        let stop = Arc::clone(&state.hint_stop);
+        state.new_game();
+        assert!(stop.load(Ordering::Relaxed));
+        assert!(state.hint_rx.is_none());
+    }
+}
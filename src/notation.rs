@@ -0,0 +1,204 @@
+// Moves typed as text: coordinates like "e2e4" or "e7e8n", or SAN like "Nf3", "exd5",
+// "O-O" or "e8=Q+". Played moves are written back as SAN.
+
+use crate::engine;
+
+const PAWN: i8 = 1;
+const KNIGHT: i8 = 2;
+const BISHOP: i8 = 3;
+const ROOK: i8 = 4;
+const QUEEN: i8 = 5;
+const KING: i8 = 6;
+
+/// Engine index of a square like "e4"; the engine counts files from h to a.
+pub fn square(s: &str) -> Option<i8> {
+    match s.as_bytes() {
+        &[file @ b'a'..=b'h', rank @ b'1'..=b'8'] => {
+            Some((rank - b'1') as i8 * 8 + (b'h' - file) as i8)
+        }
+        _ => None,
+    }
+}
+
+/// Name of an engine square, e.g. "e4".
+fn square_name(square: i8) -> String {
+    [
+        (b'h' - (square % 8) as u8) as char,
+        (b'1' + (square / 8) as u8) as char,
+    ]
+    .iter()
+    .collect()
+}
+
+/// Absolute figure ID for a promotion letter in either case.
+fn promotion_piece(c: char) -> Option<i8> {
+    match c.to_ascii_uppercase() {
+        'N' => Some(KNIGHT),
+        'B' => Some(BISHOP),
+        'R' => Some(ROOK),
+        'Q' => Some(QUEEN),
+        _ => None,
+    }
+}
+
+/// The legal move of the side to move that `text` describes: from, to and the absolute
+/// figure ID a pawn promotes to (queen for other moves). None if the text is not a move,
+/// the move is illegal, or a SAN move is ambiguous.
+pub fn parse_move(g: &mut engine::Game, text: &str) -> Option<(i8, i8, i8)> {
+    let text = text.trim().trim_end_matches(['+', '#', '!', '?']);
+    let legal = engine::legal_moves(g);
+    let board = engine::get_board(g);
+
+    // Castling, with letter O or digit 0.
+    let castling = match text {
+        "O-O" | "0-0" => Some(-2),
+        "O-O-O" | "0-0-0" => Some(2),
+        _ => None,
+    };
+    if let Some(step) = castling {
+        return legal
+            .into_iter()
+            .find(|&(from, to)| board[from as usize].abs() == KING && to - from == step)
+            .map(|(from, to)| (from, to, QUEEN));
+    }
+
+    // Coordinates, optionally with a '-' and a promotion letter.
+    let plain: String = text.chars().filter(|&c| c != '-').collect();
+    if let (Some(from), Some(to)) = (
+        plain.get(0..2).and_then(square),
+        plain.get(2..4).and_then(square),
+    ) {
+        let promotion = match plain[4..].chars().next() {
+            None => QUEEN,
+            Some(c) => promotion_piece(c)?,
+        };
+        return legal.contains(&(from, to)).then_some((from, to, promotion));
+    }
+
+    // SAN: [piece][file][rank][x]square[=promotion]
+    let mut san: String = text.chars().filter(|&c| c != 'x' && c != '=').collect();
+    let mut promotion = QUEEN;
+    if let Some(last) = san.chars().last()
+        && last.is_ascii_uppercase()
+        && san.len() > 2
+    {
+        promotion = promotion_piece(last)?;
+        san.pop();
+    }
+    let (piece, rest) = match san.chars().next()? {
+        'N' => (KNIGHT, &san[1..]),
+        'B' => (BISHOP, &san[1..]),
+        'R' => (ROOK, &san[1..]),
+        'Q' => (QUEEN, &san[1..]),
+        'K' => (KING, &san[1..]),
+        _ => (PAWN, &san[..]),
+    };
+    let to = square(rest.get(rest.len().checked_sub(2)?..)?)?;
+    let hint = &rest[..rest.len() - 2];
+    let fits = |from: i8| hint.chars().all(|c| square_name(from).contains(c));
+    let mut candidates: Vec<_> = legal
+        .into_iter()
+        .filter(|&(from, dst)| dst == to && board[from as usize].abs() == piece && fits(from))
+        .collect();
+    // The engine lists promotions once per promotion piece.
+    candidates.dedup();
+    match candidates[..] {
+        [(from, to)] => Some((from, to, promotion)),
+        _ => None,
+    }
+}
+
+/// Play the legal move `from`-`to` of the side to move, promoting to the absolute figure
+/// ID `promote_to` if it is a promotion, and return it in SAN.
+pub fn play_move(g: &mut engine::Game, from: i8, to: i8, promote_to: i8) -> String {
+    let board = engine::get_board(g);
+    let piece = board[from as usize].abs();
+    let mut san = String::new();
+    if piece == KING && (to - from).abs() == 2 {
+        san.push_str(if to < from { "O-O" } else { "O-O-O" });
+    } else if piece == PAWN {
+        if from % 8 != to % 8 {
+            san.push(square_name(from).as_bytes()[0] as char);
+            san.push('x');
+        }
+        san.push_str(&square_name(to));
+        if to / 8 == 0 || to / 8 == 7 {
+            san.push('=');
+            san.push(b" PNBRQK"[promote_to as usize] as char);
+        }
+    } else {
+        san.push(b" PNBRQK"[piece as usize] as char);
+        // Other pieces of the same kind that can reach `to` require the file, the rank
+        // or both of `from`.
+        let rivals: Vec<i8> = engine::legal_moves(g)
+            .into_iter()
+            .filter(|&(f, t)| t == to && f != from && board[f as usize].abs() == piece)
+            .map(|(f, _)| f)
+            .collect();
+        let name = square_name(from);
+        if !rivals.is_empty() {
+            if rivals.iter().all(|&f| f % 8 != from % 8) {
+                san.push_str(&name[..1]);
+            } else if rivals.iter().all(|&f| f / 8 != from / 8) {
+                san.push_str(&name[1..]);
+            } else {
+                san.push_str(&name);
+            }
+        }
+        if board[to as usize] != 0 {
+            san.push('x');
+        }
+        san.push_str(&square_name(to));
+    }
+    engine::do_move_promoting(g, from, to, promote_to, false);
+    if engine::king_in_check(g).is_some() {
+        san.push(if engine::game_state(g) == engine::STATE_CHECKMATE {
+            '#'
+        } else {
+            '+'
+        });
+    }
+    san
+}
+
+/// Play the move `text` describes, if it is a legal move of the side to move; returns
+/// from, to, the absolute figure ID for a promotion and the flag of `do_move_promoting`.
+pub fn apply_san(g: &mut engine::Game, text: &str) -> Option<(i8, i8, i8, i32)> {
+    let (from, to, promote_to) = parse_move(g, text)?;
+    if !engine::move_is_valid2(g, from as i64, to as i64) {
+        return None;
+    }
+    let flag = engine::do_move_promoting(g, from, to, promote_to, false);
+    Some((from, to, promote_to, flag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(fen: &str) -> engine::Game {
+        let mut g = engine::new_position();
+        engine::set_fen(&mut g, fen).unwrap();
+        g
+    }
+
+    #[test]
+    fn san_names_the_file_of_the_moving_knight() {
+        let fen = "rnbqkbnr/pppppppp/8/8/3P4/5N2/PPP1PPPP/RNBQKB1R w KQkq - 0 1";
+        let mut g = position(fen);
+        let (b1, d2) = (square("b1").unwrap(), square("d2").unwrap());
+        assert_eq!(parse_move(&mut g, "Nd2"), None);
+        assert_eq!(parse_move(&mut g, "Nbd2"), Some((b1, d2, QUEEN)));
+        assert_eq!(play_move(&mut g, b1, d2, QUEEN), "Nbd2");
+    }
+
+    #[test]
+    fn san_promotion() {
+        let mut g = position("8/4P3/8/8/8/k7/8/4K3 w - - 0 1");
+        let (e7, e8) = (square("e7").unwrap(), square("e8").unwrap());
+        assert_eq!(parse_move(&mut g, "e8=N"), Some((e7, e8, KNIGHT)));
+        assert_eq!(parse_move(&mut g, "e8Q"), Some((e7, e8, QUEEN)));
+        assert_eq!(play_move(&mut g, e7, e8, QUEEN), "e8=Q");
+        assert_eq!(engine::get_board(&g)[e8 as usize], QUEEN);
+    }
+}
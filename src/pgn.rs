@@ -0,0 +1,174 @@
+// PGN support: game metadata for the tag section, and reading tags and moves back.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Editable metadata of a game, written as PGN header tags.
+#[derive(Clone, Debug)]
+pub struct GameInfo {
+    pub event: String,
+    pub site: String,
+    pub round: String,
+    /// Date as PGN expects it, "YYYY.MM.DD"; unknown parts are written as '?'.
+    pub date: String,
+}
+
+impl Default for GameInfo {
+    fn default() -> Self {
+        Self {
+            event: "Casual game".into(),
+            site: "?".into(),
+            round: "-".into(),
+            date: today(),
+        }
+    }
+}
+
+impl GameInfo {
+    /// The seven tag roster, with `white`, `black` and `result` supplied by the caller.
+    pub fn headers(&self, white: &str, black: &str, result: &str) -> String {
+        let date = normalize_date(&self.date).unwrap_or_else(|| "????.??.??".into());
+        let tag = |name: &str, value: &str| {
+            let value = if value.trim().is_empty() {
+                "?"
+            } else {
+                value.trim()
+            };
+            format!(
+                "[{name} \"{}\"]\n",
+                value.replace('\\', "\\\\").replace('"', "\\\"")
+            )
+        };
+        [
+            tag("Event", &self.event),
+            tag("Site", &self.site),
+            tag("Date", &date),
+            tag("Round", &self.round),
+            tag("White", white),
+            tag("Black", black),
+            tag("Result", result),
+        ]
+        .concat()
+    }
+}
+
+/// Today's date in PGN format, e.g. "2026.03.11" (UTC).
+pub fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (y, m, d) = civil_from_days((secs / 86_400) as i64);
+    format!("{y:04}.{m:02}.{d:02}")
+}
+
+/// Gregorian date from days since 1970-01-01 (Howard Hinnant's algorithm).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + (m <= 2) as i64;
+    (y, m, d)
+}
+
+/// Bring a date into PGN form "YYYY.MM.DD", also accepting '-' or '/' as separators.
+/// Unknown parts may be given as '?'; returns `None` for anything else.
+pub fn normalize_date(date: &str) -> Option<String> {
+    let parts: Vec<&str> = date.trim().split(['.', '-', '/']).collect();
+    let [y, m, d] = parts[..] else {
+        return None;
+    };
+    let field = |s: &str, len: usize, max: u32| -> Option<String> {
+        if !s.is_empty() && s.chars().all(|c| c == '?') {
+            return Some("?".repeat(len));
+        }
+        let n: u32 = s.parse().ok()?;
+        (s.len() <= len && (1..=max).contains(&n)).then(|| format!("{n:0len$}"))
+    };
+    Some(format!(
+        "{}.{}.{}",
+        field(y, 4, 9999)?,
+        field(m, 2, 12)?,
+        field(d, 2, 31)?
+    ))
+}
+
+/// Value of the tag `name` in the tag section of `pgn`, e.g. the position of a "FEN" tag.
+pub fn tag_value(pgn: &str, name: &str) -> Option<String> {
+    pgn.lines().find_map(|line| {
+        let inner = line.trim().strip_prefix('[')?.strip_suffix(']')?;
+        let (tag, value) = inner.split_once(char::is_whitespace)?;
+        let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+        (tag == name).then(|| value.replace("\\\"", "\"").replace("\\\\", "\\"))
+    })
+}
+
+/// The moves of the movetext in order, without move numbers, comments, variations,
+/// annotation glyphs and the result.
+pub fn movetext_moves(pgn: &str) -> Vec<String> {
+    let mut text = String::new();
+    let mut comment = false;
+    let mut variation = 0;
+    for line in pgn
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('['))
+    {
+        for c in line.chars() {
+            match c {
+                _ if comment => comment = c != '}',
+                '{' => comment = true,
+                ';' if variation == 0 => break,
+                '(' => variation += 1,
+                ')' => variation -= 1,
+                _ if variation == 0 => text.push(c),
+                _ => {}
+            }
+        }
+        text.push(' ');
+    }
+    text.split_whitespace()
+        .filter(|token| !matches!(*token, "1-0" | "0-1" | "1/2-1/2" | "*"))
+        .map(|token| {
+            // A move number may be written as "12.", "12..." or joined to the move, "12.e4".
+            let after_number = token.trim_start_matches(|c: char| c.is_ascii_digit());
+            match after_number.strip_prefix('.') {
+                Some(rest) if after_number.len() < token.len() => rest.trim_start_matches('.'),
+                _ => token,
+            }
+        })
+        .filter(|token| !token.is_empty() && !token.starts_with('$'))
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn movetext_skips_comments_variations_and_glyphs() {
+        let pgn = "[Event \"Test\"]\n[Result \"1-0\"]\n\n\
+                   1. e4 {best by test} e5 (1... c5 2. Nf3 (2. c3) d6) 2. Nf3 $1 Nc6 ; rest of line\n\
+                   3.Bb5 a6 4. Ba4 4... Nf6 1-0\n";
+        assert_eq!(
+            movetext_moves(pgn),
+            ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6"]
+        );
+    }
+
+    #[test]
+    fn tags_are_unescaped() {
+        let info = GameInfo {
+            event: "The \"Open\"".into(),
+            date: "2026-3-1".into(),
+            ..GameInfo::default()
+        };
+        let pgn = info.headers("A", "B", "*");
+        assert_eq!(tag_value(&pgn, "Event").as_deref(), Some("The \"Open\""));
+        assert_eq!(tag_value(&pgn, "Date").as_deref(), Some("2026.03.01"));
+        assert_eq!(tag_value(&pgn, "Annotator"), None);
+    }
+}
@@ -0,0 +1,126 @@
+// Image based piece rendering, as an alternative to Unicode glyphs.
+// Each bundled set is a handful of small SVG templates, rasterized once when first shown.
+
+use std::sync::{Arc, OnceLock};
+
+use resvg::{tiny_skia, usvg};
+use xilem::Blob;
+use xilem::peniko::{ImageAlphaType, ImageBrush, ImageData, ImageFormat};
+
+use crate::{ColoredPiece, Piece, Side};
+
+/// Edge length of the rasterized piece images in pixels.
+const IMAGE_SIZE: u32 = 128;
+
+/// The bundled image sets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageSet {
+    /// Classic outlines.
+    Simple,
+    /// Flat shapes built from straight lines and circles.
+    Geometric,
+}
+
+impl ImageSet {
+    pub fn name(self) -> &'static str {
+        match self {
+            ImageSet::Simple => "Simple",
+            ImageSet::Geometric => "Geometric",
+        }
+    }
+
+    fn templates(self) -> &'static [&'static str; 6] {
+        match self {
+            ImageSet::Simple => &SIMPLE_SET,
+            ImageSet::Geometric => &GEOMETRIC_SET,
+        }
+    }
+}
+
+/// SVG templates in the order Pawn .. King; `{fill}` and `{stroke}` are replaced per side.
+const SIMPLE_SET: [&str; 6] = [
+    include_str!("../resources/pieces/simple/pawn.svg"),
+    include_str!("../resources/pieces/simple/knight.svg"),
+    include_str!("../resources/pieces/simple/bishop.svg"),
+    include_str!("../resources/pieces/simple/rook.svg"),
+    include_str!("../resources/pieces/simple/queen.svg"),
+    include_str!("../resources/pieces/simple/king.svg"),
+];
+
+const GEOMETRIC_SET: [&str; 6] = [
+    include_str!("../resources/pieces/geometric/pawn.svg"),
+    include_str!("../resources/pieces/geometric/knight.svg"),
+    include_str!("../resources/pieces/geometric/bishop.svg"),
+    include_str!("../resources/pieces/geometric/rook.svg"),
+    include_str!("../resources/pieces/geometric/queen.svg"),
+    include_str!("../resources/pieces/geometric/king.svg"),
+];
+
+/// Rasterized images, indexed by [side][piece].
+pub struct PieceImages {
+    images: [[ImageBrush; 6]; 2],
+}
+
+impl PieceImages {
+    pub fn get(&self, piece: ColoredPiece) -> &ImageBrush {
+        let side = match piece.side {
+            Side::White => 0,
+            Side::Black => 1,
+        };
+        let idx = match piece.piece {
+            Piece::Pawn => 0,
+            Piece::Knight => 1,
+            Piece::Bishop => 2,
+            Piece::Rook => 3,
+            Piece::Queen => 4,
+            Piece::King => 5,
+        };
+        &self.images[side][idx]
+    }
+}
+
+fn rasterize(template: &str, fill: &str, stroke: &str) -> Option<ImageBrush> {
+    let svg = template.replace("{fill}", fill).replace("{stroke}", stroke);
+    let tree = usvg::Tree::from_str(&svg, &usvg::Options::default()).ok()?;
+    let mut pixmap = tiny_skia::Pixmap::new(IMAGE_SIZE, IMAGE_SIZE)?;
+    let scale = IMAGE_SIZE as f32 / tree.size().width().max(tree.size().height());
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    let data = ImageData {
+        data: Blob::new(Arc::new(pixmap.take())),
+        format: ImageFormat::Rgba8,
+        alpha_type: ImageAlphaType::AlphaPremultiplied,
+        width: IMAGE_SIZE,
+        height: IMAGE_SIZE,
+    };
+    Some(ImageBrush::new(data))
+}
+
+fn load(set: ImageSet) -> Option<PieceImages> {
+    let mut sides: Vec<[ImageBrush; 6]> = Vec::with_capacity(2);
+    for (fill, stroke) in [("#ffffff", "#000000"), ("#000000", "#ffffff")] {
+        let images = set
+            .templates()
+            .iter()
+            .map(|t| rasterize(t, fill, stroke))
+            .collect::<Option<Vec<_>>>()?;
+        sides.push(images.try_into().ok()?);
+    }
+    Some(PieceImages {
+        images: sides.try_into().ok()?,
+    })
+}
+
+/// The images of a bundled set, or `None` if it failed to load; callers fall back to Unicode.
+pub fn piece_images(set: ImageSet) -> Option<&'static PieceImages> {
+    static SIMPLE: OnceLock<Option<PieceImages>> = OnceLock::new();
+    static GEOMETRIC: OnceLock<Option<PieceImages>> = OnceLock::new();
+    let cache = match set {
+        ImageSet::Simple => &SIMPLE,
+        ImageSet::Geometric => &GEOMETRIC,
+    };
+    cache.get_or_init(|| load(set)).as_ref()
+}
@@ -0,0 +1,76 @@
+// Headless play driven by text on stdin, for scripts and external tools.
+// echo "e2e4 e7e5 Nf3 go" | cargo run --release -- --stdin
+
+use std::io::{self, BufRead};
+use std::sync::atomic::AtomicBool;
+
+use crate::engine;
+use crate::notation;
+
+/// The board as text, rank 8 at the top; White's pieces are upper case.
+fn diagram(board: &engine::Board) -> String {
+    let mut text = String::new();
+    for rank in (0..8).rev() {
+        text.push((b'1' + rank as u8) as char);
+        for file in (0..8).rev() {
+            let figure = board[rank * 8 + file];
+            let c = match figure.abs() {
+                1 => 'p',
+                2 => 'n',
+                3 => 'b',
+                4 => 'r',
+                5 => 'q',
+                6 => 'k',
+                _ => '.',
+            };
+            text.push(' ');
+            text.push(if figure > 0 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            });
+        }
+        text.push('\n');
+    }
+    text.push_str("  a b c d e f g h\n");
+    text
+}
+
+/// Read whitespace separated moves in coordinate or SAN form from stdin and play them;
+/// "go" lets the engine play the next move. The board is printed after each move,
+/// invalid input is reported on stderr and skipped.
+pub fn run(secs_per_move: f32) {
+    let mut g = engine::new_game();
+    g.secs_per_move = secs_per_move;
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        for word in line.split_whitespace() {
+            if engine::game_state(&mut g) != engine::STATE_PLAYING {
+                eprintln!("Game over, ignoring {word}");
+                continue;
+            }
+            let (from, to, promote_to) = if word == "go" {
+                let mv = engine::reply(&mut g, &AtomicBool::new(false));
+                (mv.src as i8, mv.dst as i8, mv.promotion())
+            } else {
+                match notation::parse_move(&mut g, word) {
+                    Some(mv) => mv,
+                    None => {
+                        eprintln!("Invalid move: {word}");
+                        continue;
+                    }
+                }
+            };
+            let flag = engine::do_move_promoting(&mut g, from, to, promote_to, false);
+            println!("{}", engine::move_to_str(&g, from, to, flag).trim());
+            print!("{}", diagram(&engine::get_board(&g)));
+            match engine::game_state(&mut g) {
+                engine::STATE_CHECKMATE => println!("Checkmate"),
+                engine::STATE_STALEMATE => println!("Stalemate"),
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,64 @@
+// User preferences kept between runs, as TOML in the platform's config directory.
+// Linux: $XDG_CONFIG_HOME or ~/.config; macOS: ~/Library/Application Support; Windows: %APPDATA%.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Saved preferences. A missing or unreadable entry is `None`, and the app keeps its
+/// default for it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub time_per_move: Option<f64>,
+    pub solid_unicode: Option<bool>,
+    pub rotated: Option<bool>,
+    pub engine_plays_white: Option<bool>,
+    pub engine_plays_black: Option<bool>,
+    /// Theme colors as RGB.
+    pub light: Option<[u8; 3]>,
+    pub dark: Option<[u8; 3]>,
+    pub piece: Option<[u8; 3]>,
+    pub dark_mode: Option<bool>,
+    /// Pawn, knight, bishop, rook and queen, for the material display.
+    pub piece_values: Option<[f64; 5]>,
+    /// Inner size of the window in logical pixels.
+    pub window_size: Option<[f64; 2]>,
+}
+
+fn config_dir() -> Option<PathBuf> {
+    let var = |name| {
+        std::env::var_os(name)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    if cfg!(target_os = "windows") {
+        var("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        var("XDG_CONFIG_HOME").or_else(|| var("HOME").map(|home| home.join(".config")))
+    }
+}
+
+fn path() -> Option<PathBuf> {
+    Some(config_dir()?.join("xilem-chess").join("settings.toml"))
+}
+
+/// The saved settings; empty if there are none or the file is not valid TOML.
+pub fn load() -> Settings {
+    path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|text| toml::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+/// Write `settings`, creating the config directory if needed.
+pub fn save(settings: &Settings) -> Result<(), String> {
+    let path = path().ok_or("no config directory")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|err| format!("{}: {err}", dir.display()))?;
+    }
+    let text = toml::to_string(settings).map_err(|err| err.to_string())?;
+    std::fs::write(&path, text).map_err(|err| format!("{}: {err}", path.display()))
+}
@@ -0,0 +1,106 @@
+// Spoken move announcements, using the platform's text-to-speech command.
+// Linux: espeak-ng, espeak or spd-say; macOS: say; Windows: PowerShell System.Speech.
+
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Turn engine notation like "N_G1-F3 +" or "  E7xD8" into words, e.g. "Knight f3, check".
+pub fn describe(notation: &str, checkmate: bool) -> String {
+    let (mv, check) = match notation
+        .strip_suffix(" +")
+        .or_else(|| notation.strip_suffix(" #"))
+    {
+        Some(mv) => (mv, true),
+        None => (notation, false),
+    };
+    let mv = mv.trim_end_matches(" e.p.");
+    let (mv, promotion) = match mv.split_once('=') {
+        Some((mv, "N")) => (mv, Some("knight")),
+        Some((mv, "B")) => (mv, Some("bishop")),
+        Some((mv, "R")) => (mv, Some("rook")),
+        Some((mv, _)) => (mv, Some("queen")),
+        None => (mv, None),
+    };
+    let mut text = match mv {
+        "o-o" => "Castles kingside".to_string(),
+        "o-o-o" => "Castles queenside".to_string(),
+        _ => {
+            let piece = match mv.get(0..2) {
+                Some("N_") => "Knight ",
+                Some("B_") => "Bishop ",
+                Some("R_") => "Rook ",
+                Some("Q_") => "Queen ",
+                Some("K_") => "King ",
+                _ => "",
+            };
+            let squares = mv.get(2..).unwrap_or_default().to_lowercase();
+            match squares.split_once('x') {
+                Some((from, to)) if piece.is_empty() => format!("{} takes {to}", &from[..1]),
+                Some((_, to)) => format!("{piece}takes {to}"),
+                None => {
+                    let to = squares.split_once('-').map_or(&*squares, |(_, to)| to);
+                    format!("{piece}{to}")
+                }
+            }
+        }
+    };
+    if let Some(piece) = promotion {
+        text.push_str(&format!(", promotes to {piece}"));
+    }
+    if checkmate {
+        text.push_str(", checkmate");
+    } else if check {
+        text.push_str(", check");
+    }
+    text
+}
+
+fn speech_command(text: &str) -> Command {
+    if cfg!(target_os = "macos") {
+        let mut cmd = Command::new("say");
+        cmd.arg(text);
+        cmd
+    } else if cfg!(target_os = "windows") {
+        let mut cmd = Command::new("powershell");
+        cmd.args([
+            "-NoProfile",
+            "-Command",
+            &format!(
+                "Add-Type -AssemblyName System.Speech; \
+                 (New-Object System.Speech.Synthesis.SpeechSynthesizer).Speak('{}')",
+                text.replace('\'', "''")
+            ),
+        ]);
+        cmd
+    } else {
+        let mut cmd = Command::new("espeak-ng");
+        cmd.arg(text);
+        cmd
+    }
+}
+
+/// Speak `text` in the background; failures are reported on stderr only.
+pub fn say(text: String) {
+    thread::spawn(move || {
+        let mut result = speech_command(&text)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        // Fall back to the older Linux speech tools.
+        if cfg!(target_os = "linux") && result.is_err() {
+            for tool in ["espeak", "spd-say"] {
+                result = Command::new(tool)
+                    .arg(&text)
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status();
+                if result.is_ok() {
+                    break;
+                }
+            }
+        }
+        if let Err(err) = result {
+            eprintln!("Text-to-speech failed: {err}");
+        }
+    });
+}
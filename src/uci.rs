@@ -0,0 +1,204 @@
+// Engines the GUI can ask for a move: the built-in one, or an external engine like
+// Stockfish speaking the Universal Chess Interface (UCI) on stdin and stdout.
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crate::engine;
+use crate::notation;
+
+/// How long an external engine may take to answer "uci" and "isready".
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Polling interval for a stop request while an external engine searches.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A source of engine moves.
+pub trait Backend: Send {
+    /// The move to play for the side to move in `game`, which is left unchanged. Setting
+    /// `stop` ends the search early with the best move found so far.
+    fn best_move(
+        &mut self,
+        game: &mut engine::Game,
+        stop: &AtomicBool,
+    ) -> Result<engine::Move, String>;
+}
+
+/// The built-in engine.
+pub struct BuiltIn;
+
+impl Backend for BuiltIn {
+    fn best_move(
+        &mut self,
+        game: &mut engine::Game,
+        stop: &AtomicBool,
+    ) -> Result<engine::Move, String> {
+        Ok(engine::reply(game, stop))
+    }
+}
+
+/// A running external UCI engine.
+pub struct Uci {
+    path: String,
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines the engine writes, read by a separate thread so that waiting can time out.
+    lines: mpsc::Receiver<String>,
+}
+
+impl Uci {
+    /// Start the engine at `path` and wait until it is ready.
+    pub fn start(path: &str) -> Result<Uci, String> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| format!("cannot start {path}: {err}"))?;
+        let stdin = child.stdin.take().ok_or("no stdin")?;
+        let stdout = child.stdout.take().ok_or("no stdout")?;
+        let (tx, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut uci = Uci {
+            path: path.to_string(),
+            child,
+            stdin,
+            lines,
+        };
+        uci.send("uci")?;
+        uci.wait_for("uciok")?;
+        uci.send("isready")?;
+        uci.wait_for("readyok")?;
+        Ok(uci)
+    }
+
+    /// Path the engine was started from.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{command}").map_err(|_| "engine exited".to_string())
+    }
+
+    /// Skip lines up to one starting with `token`, during the handshake.
+    fn wait_for(&mut self, token: &str) -> Result<(), String> {
+        loop {
+            let line = self
+                .lines
+                .recv_timeout(HANDSHAKE_TIMEOUT)
+                .map_err(|err| match err {
+                    mpsc::RecvTimeoutError::Timeout => format!("no {token} from the engine"),
+                    mpsc::RecvTimeoutError::Disconnected => "engine exited".to_string(),
+                })?;
+            if line.split_whitespace().next() == Some(token) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Backend for Uci {
+    fn best_move(
+        &mut self,
+        game: &mut engine::Game,
+        stop: &AtomicBool,
+    ) -> Result<engine::Move, String> {
+        self.send(&format!("position fen {}", engine::to_fen(game)))?;
+        if game.max_depth > 0 {
+            self.send(&format!("go depth {}", game.max_depth))?;
+        } else {
+            let millis = (game.secs_per_move * 1000.0).round() as u64;
+            self.send(&format!("go movetime {}", millis.max(1)))?;
+        }
+
+        let mut chess_move = engine::Move::default();
+        chess_move.state = engine::STATE_PLAYING;
+        let mut stopped = false;
+        let bestmove = loop {
+            if !stopped && stop.load(Ordering::Relaxed) {
+                self.send("stop")?;
+                stopped = true;
+            }
+            let line = match self.lines.recv_timeout(POLL_INTERVAL) {
+                Ok(line) => line,
+                Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Err("engine exited".into()),
+            };
+            match line.split_whitespace().next() {
+                Some("info") => read_info(&line, &mut chess_move),
+                Some("bestmove") => break line,
+                _ => {}
+            }
+        };
+
+        let text = bestmove.split_whitespace().nth(1).unwrap_or("");
+        let (from, to, promote_to) = notation::parse_move(game, text)
+            .ok_or_else(|| format!("engine sent an illegal move: {text}"))?;
+        chess_move.src = from as i64;
+        chess_move.dst = to as i64;
+        // A promotion comes with the piece letter, like "e7e8q".
+        if text.len() == 5 {
+            let side = if engine::turn(game) == 0 { 1 } else { -1 };
+            chess_move.promote_to = side * promote_to as i64;
+        }
+        Ok(chess_move)
+    }
+}
+
+impl Drop for Uci {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Take depth, score and node count from an "info" line. Scores are from the view of the
+/// side to move, like the built-in engine's; mates count as the king's value.
+fn read_info(line: &str, chess_move: &mut engine::Move) {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let value = |key: &str| {
+        let pos = words.iter().position(|&w| w == key)?;
+        words.get(pos + 1)?.parse::<i64>().ok()
+    };
+    if let Some(depth) = value("depth") {
+        chess_move.depth = depth as i32;
+    }
+    if let Some(nodes) = value("nodes") {
+        chess_move.nodes = nodes as u64;
+    }
+    if let Some(cp) = value("cp") {
+        chess_move.score = cp;
+        chess_move.checkmate_in = 0;
+    } else if let Some(moves) = value("mate") {
+        let king = engine::KING_VALUE as i64;
+        chess_move.score = if moves > 0 {
+            king - moves
+        } else {
+            -king - moves
+        };
+        chess_move.checkmate_in = (2 * moves.abs()).min(i8::MAX as i64) as i8;
+    }
+}
+
+/// The external engine at `path` in `slot`, started first if none or another one runs.
+pub fn connect<'a>(slot: &'a mut Option<Uci>, path: &str) -> Result<&'a mut Uci, String> {
+    if slot.as_ref().is_none_or(|uci| uci.path() != path) {
+        *slot = None;
+        *slot = Some(Uci::start(path)?);
+    }
+    Ok(slot.as_mut().unwrap())
+}